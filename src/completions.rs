@@ -0,0 +1,205 @@
+//! Completion of environment keys and variable names.
+//!
+//! The completion scripts generated by clap only know the static parts of
+//! the command line. For bash, zsh and fish they are extended to complete
+//! environment keys with `envi keys` and variable names with
+//! `envi names <KEY>`, passing along the `-i` options of the command line.
+
+use regex::Regex;
+use structopt::clap::Shell;
+
+/// Adds the completion of keys and variable names to `script`, the
+/// completion script clap generated for `shell`.
+pub fn add_dynamic_completions(shell: Shell, script: String) -> String {
+    match shell {
+        Shell::Bash => format!("{}\n{}\n", script.trim_end(), BASH),
+        Shell::Zsh => zsh(&script),
+        Shell::Fish => format!("{}\n{}\n", script.trim_end(), FISH),
+        _ => script,
+    }
+}
+
+/// Completes the key arguments with `_envi_keys` instead of files, and
+/// `--name` and the variables of `unset` and `encrypt` with `_envi_names`.
+fn zsh(script: &str) -> String {
+    let names =
+        Regex::new(r"(?m)^(':args -- [^:]*the variable to (?:remove|encrypt)[^:]*):_files' \\$")
+            .unwrap();
+    let script = names.replace_all(
+        script,
+        "$1:_envi_keys' \\\n'::name -- Name of the variable:_envi_names' \\",
+    );
+
+    let keys = Regex::new(r"(?m)^('::?(?:key|keys|args)(?: -- [^:]*)?):_files' \\$").unwrap();
+    let script = keys.replace_all(&script, "$1:_envi_keys' \\");
+
+    let script = script.replace(
+        "'*--name=[Variable name to show value for]'",
+        "'*--name=[Variable name to show value for]: :_envi_names'",
+    );
+
+    match script.trim_end().strip_suffix("_envi \"$@\"") {
+        Some(script) => format!("{}{}\n\n_envi \"$@\"\n", script, ZSH),
+        None => script,
+    }
+}
+
+const BASH: &str = r#"_envi_config_args() {
+    local i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -i|--input-file) printf '%s\n' -i "${COMP_WORDS[i+1]}" ;;
+        esac
+    done
+}
+
+_envi_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local args=() config_args=() words="" skip=0 i
+
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        if ((skip)); then
+            skip=0
+            continue
+        fi
+        case "${COMP_WORDS[i]}" in
+            --) _envi "$@"; return ;;
+            -i|--input-file|-o|--output-file|--output|--format|--name|--color|--prefix|--pid|--from-file) skip=1 ;;
+            -*) ;;
+            *) args+=("${COMP_WORDS[i]}") ;;
+        esac
+    done
+    mapfile -t config_args < <(_envi_config_args)
+
+    if ((skip)); then
+        if [[ "$prev" == --name && "${args[0]}" == show && ${#args[@]} -ge 2 ]]; then
+            words="$(envi "${config_args[@]}" names "${args[1]}" 2>/dev/null)"
+        fi
+    elif [[ "$cur" != -* ]]; then
+        case "${args[0]}:${#args[@]}" in
+            show:1|ediff:1|exec:1|names:1|set:1|unset:1|encrypt:1|diff:1|diff:2|matrix:*)
+                words="$(envi "${config_args[@]}" keys 2>/dev/null)" ;;
+            unset:2|encrypt:2)
+                words="$(envi "${config_args[@]}" names "${args[1]}" 2>/dev/null)" ;;
+        esac
+    fi
+
+    if [[ -n "$words" ]]; then
+        COMPREPLY=($(compgen -W "$words" -- "$cur"))
+        return 0
+    fi
+    _envi "$@"
+}
+
+complete -F _envi_dynamic -o bashdefault -o default envi"#;
+
+const ZSH: &str = r#"
+_envi_config_args() {
+    local -a all
+    local i
+    all=(${(Q)${(z)BUFFER}})
+    for ((i = 1; i < ${#all}; i++)); do
+        case "${all[i]}" in
+            -i|--input-file) print -r -- -i; print -r -- "${all[i+1]}" ;;
+        esac
+    done
+}
+
+(( $+functions[_envi_keys] )) ||
+_envi_keys() {
+    local -a keys
+    keys=(${(f)"$(envi ${(f)"$(_envi_config_args)"} keys 2>/dev/null)"})
+    _describe -t keys 'environment key' keys
+}
+
+(( $+functions[_envi_names] )) ||
+_envi_names() {
+    local -a names
+    names=(${(f)"$(envi ${(f)"$(_envi_config_args)"} names "${line[1]}" 2>/dev/null)"})
+    _describe -t names 'variable name' names
+}"#;
+
+const FISH: &str = r#"function __envi_config_args
+    set -l tokens (commandline -opc)
+    for i in (seq (count $tokens))
+        if contains -- $tokens[$i] -i --input-file; and test $i -lt (count $tokens)
+            printf '%s\n' -i $tokens[(math $i + 1)]
+        end
+    end
+end
+
+function __envi_args
+    set -l skip 0
+    for token in (commandline -opc)[2..-1]
+        if test $skip = 1
+            set skip 0
+            continue
+        end
+        switch $token
+            case -- --
+                return
+            case -i --input-file -o --output-file --output --format --name --color --prefix --pid --from-file
+                set skip 1
+            case '-*'
+            case '*'
+                echo $token
+        end
+    end
+end
+
+function __envi_complete_key
+    set -l last (commandline -opc)[-1]
+    if contains -- $last -i --input-file -o --output-file --output --format --name --color --prefix --pid --from-file
+        return 1
+    end
+    set -l args (__envi_args)
+    switch "$args[1]"
+        case show ediff exec names set unset encrypt
+            test (count $args) -eq 1
+        case diff
+            test (count $args) -le 2
+        case matrix
+            true
+        case '*'
+            false
+    end
+end
+
+function __envi_complete_name
+    set -l args (__envi_args)
+    contains -- "$args[1]" unset encrypt; and test (count $args) -eq 2
+end
+
+complete -c envi -n __envi_complete_key -f -a "(envi (__envi_config_args) keys 2>/dev/null)"
+complete -c envi -n __envi_complete_name -f -a "(envi (__envi_config_args) names (__envi_args)[2] 2>/dev/null)"
+complete -c envi -n "__fish_seen_subcommand_from show" -l name -x -a "(envi (__envi_config_args) names (__envi_args)[2] 2>/dev/null)""#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zsh() {
+        let script = r#"_envi() {
+':keys:_files' \
+':key -- Name of the environment key to use:_files' \
+'--output-file=[Ouput file]: :_files' \
+':args -- Name of the environment key to use and the variable to remove:_files' \
+'*--name=[Variable name to show value for]' \
+}
+
+_envi "$@""#;
+
+        let res = zsh(script);
+
+        assert!(res.contains("':keys:_envi_keys' \\\n"));
+        assert!(res.contains("':key -- Name of the environment key to use:_envi_keys' \\\n"));
+        assert!(res.contains("'--output-file=[Ouput file]: :_files' \\\n"));
+        assert!(res.contains(
+            "the variable to remove:_envi_keys' \\\n'::name -- Name of the variable:_envi_names' \\\n"
+        ));
+        assert!(res.contains("'*--name=[Variable name to show value for]: :_envi_names'"));
+        assert!(res.contains("_envi_keys() {"));
+        assert!(res.ends_with("}\n\n_envi \"$@\"\n"));
+    }
+}
@@ -0,0 +1,910 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::file_parser::{ARRAYS_KEY, ENVIRONMENTS_KEY, EXTENDS_KEY, FLATTEN_KEY, SCHEMA_KEY};
+use crate::parser::ConfigParser;
+use crate::{is_stdin, read_input_file};
+
+/// The part of a config file a variable is edited in.
+#[derive(Debug, Clone, Copy)]
+pub enum Section<'a> {
+    /// The top level variables shared by all environments
+    Defaults,
+    /// The table of the given environment key
+    Environment(&'a str),
+}
+
+impl fmt::Display for Section<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Section::Defaults => write!(f, "the defaults"),
+            Section::Environment(key) => write!(f, "environment '{}'", key),
+        }
+    }
+}
+
+/// Sets `name` to `value` in the given section of the config file, creating
+/// the environment table if needed.
+///
+/// TOML and JSON files keep their comments, ordering and whitespace, and an
+/// existing value keeps its type when `value` parses as it. The file is read
+/// in the given `format`, or the one detected from it.
+pub fn set_variable(
+    path: &Path,
+    format: Option<&str>,
+    section: Section,
+    name: &str,
+    value: &str,
+) -> Result<()> {
+    edit_file(path, format, |content, format| match format {
+        Format::Toml => toml::set_variable(content, section, name, value),
+        Format::Json => json::set_variable(content, section, name, value),
+    })
+}
+
+/// Removes `name` from the given section of the config file.
+pub fn unset_variable(
+    path: &Path,
+    format: Option<&str>,
+    section: Section,
+    name: &str,
+) -> Result<()> {
+    edit_file(path, format, |content, format| match format {
+        Format::Toml => toml::unset_variable(content, section, name),
+        Format::Json => json::unset_variable(content, section, name),
+    })
+}
+
+/// Returns the value of `name` in the given section of the config file, as
+/// it is written there.
+pub fn get_variable(
+    path: &Path,
+    format: Option<&str>,
+    section: Section,
+    name: &str,
+) -> Result<String> {
+    let (content, format, _) = read_editable_file(path, format)?;
+
+    match format {
+        Format::Toml => toml::get_variable(&content, section, name),
+        Format::Json => json::get_variable(&content, section, name),
+    }
+    .with_context(|| format!("could not read config file '{}'", path.display()))
+}
+
+/// Fails if `name` in the given section is built from a nested table of the
+/// config file, as it can't be edited by that name.
+pub fn check_not_flattened(
+    path: &Path,
+    format: Option<&str>,
+    section: Section,
+    name: &str,
+) -> Result<()> {
+    let (content, format, parser) = read_editable_file(path, format)?;
+    let config = parser.parse(&content, path)?;
+
+    let defined = match section {
+        Section::Defaults => config.defaults.contains_key(name),
+        Section::Environment(key) => config
+            .overrides
+            .get(key)
+            .is_some_and(|vars| vars.contains_key(name)),
+    };
+    let written = match format {
+        Format::Toml => toml::contains_variable(&content, section, name),
+        Format::Json => json::contains_variable(&content, section, name),
+    }
+    .with_context(|| format!("could not read config file '{}'", path.display()))?;
+
+    if defined && !written {
+        bail!(
+            "'{}' in {} comes from a nested table of '{}' and can't be edited by that name",
+            name,
+            section,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// The formats that can be edited.
+enum Format {
+    Toml,
+    Json,
+}
+
+fn read_editable_file(
+    path: &Path,
+    format: Option<&str>,
+) -> Result<(String, Format, Arc<dyn ConfigParser>)> {
+    if is_stdin(path) {
+        bail!("the config read from stdin can't be edited, use -i to choose a file");
+    }
+
+    let (content, parser) = read_input_file(path, format)?;
+
+    match parser.name() {
+        "toml" => Ok((content, Format::Toml, parser)),
+        "json" => Ok((content, Format::Json, parser)),
+        _ => bail!(
+            "editing '{}' is not supported, only TOML and JSON config files can be edited",
+            path.display()
+        ),
+    }
+}
+
+fn edit_file<F>(path: &Path, format: Option<&str>, edit: F) -> Result<()>
+where
+    F: FnOnce(&str, Format) -> Result<String>,
+{
+    let (content, format, parser) = read_editable_file(path, format)?;
+
+    // The edited file has to be read back the same way, so a name that
+    // clashes with one built from a nested table is refused
+    let content = edit(&content, format)
+        .and_then(|content| parser.parse(&content, path).map(|_| content))
+        .with_context(|| format!("could not edit config file '{}'", path.display()))?;
+
+    std::fs::write(path, content)
+        .with_context(|| format!("could not write to file `{}`", path.display()))
+}
+
+fn check_name(section: Section, name: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!("variable name must not be empty");
+    }
+    let layout = [ENVIRONMENTS_KEY, FLATTEN_KEY, ARRAYS_KEY];
+    match section {
+        Section::Defaults if name == SCHEMA_KEY => {
+            bail!("'{}' is reserved for the variable schema", SCHEMA_KEY)
+        }
+        Section::Environment(key) if key == SCHEMA_KEY => {
+            bail!("'{}' is reserved for the variable schema", SCHEMA_KEY)
+        }
+        Section::Defaults if layout.contains(&name) => {
+            bail!("'{}' is reserved for the config layout", name)
+        }
+        Section::Environment(key) if layout.contains(&key) => {
+            bail!("'{}' is reserved for the config layout", key)
+        }
+        Section::Environment(_) if name == EXTENDS_KEY => {
+            bail!("'{}' is reserved for environment inheritance", EXTENDS_KEY)
+        }
+        _ => Ok(()),
+    }
+}
+
+mod toml {
+    use super::*;
+    use toml_edit::{value, DocumentMut, Item, Table, TableLike};
+
+    pub fn set_variable(content: &str, section: Section, name: &str, val: &str) -> Result<String> {
+        check_name(section, name)?;
+
+        let mut doc: DocumentMut = content.parse()?;
+        let table = section_table(&mut doc, section, true)?.unwrap();
+
+        match table.get_mut(name) {
+            Some(item) if item.is_table_like() => {
+                bail!("'{}' in {} is a table, not a variable", name, section)
+            }
+            Some(item) => {
+                let decor = item.as_value().map(|v| v.decor().clone());
+                *item = value(typed_value(item.as_value(), val));
+                if let (Some(decor), Some(v)) = (decor, item.as_value_mut()) {
+                    *v.decor_mut() = decor;
+                }
+            }
+            None => {
+                table.insert(name, value(val));
+            }
+        }
+
+        Ok(doc.to_string())
+    }
+
+    pub fn unset_variable(content: &str, section: Section, name: &str) -> Result<String> {
+        check_name(section, name)?;
+
+        let mut doc: DocumentMut = content.parse()?;
+        let table = match section_table(&mut doc, section, false)? {
+            Some(t) => t,
+            None => bail!("{} does not exist", section),
+        };
+
+        match table.get(name) {
+            Some(item) if item.is_table_like() => {
+                bail!("'{}' in {} is a table, not a variable", name, section)
+            }
+            Some(_) => {
+                table.remove(name);
+            }
+            None => bail!("variable '{}' is not defined in {}", name, section),
+        }
+
+        Ok(doc.to_string())
+    }
+
+    pub fn get_variable(content: &str, section: Section, name: &str) -> Result<String> {
+        let mut doc: DocumentMut = content.parse()?;
+        let table = match section_table(&mut doc, section, false)? {
+            Some(t) => t,
+            None => bail!("{} does not exist", section),
+        };
+
+        let value = match table.get(name) {
+            Some(item) if item.is_table_like() => {
+                bail!("'{}' in {} is a table, not a variable", name, section)
+            }
+            Some(item) => item.as_value(),
+            None => bail!("variable '{}' is not defined in {}", name, section),
+        };
+
+        match value {
+            Some(toml_edit::Value::String(s)) => Ok(s.value().clone()),
+            Some(toml_edit::Value::Integer(i)) => Ok(i.value().to_string()),
+            Some(toml_edit::Value::Float(f)) => Ok(f.value().to_string()),
+            Some(toml_edit::Value::Boolean(b)) => Ok(b.value().to_string()),
+            Some(toml_edit::Value::Datetime(d)) => Ok(d.value().to_string()),
+            _ => bail!("'{}' in {} is not a single value", name, section),
+        }
+    }
+
+    pub fn contains_variable(content: &str, section: Section, name: &str) -> Result<bool> {
+        let mut doc: DocumentMut = content.parse()?;
+        let table = section_table(&mut doc, section, false)?;
+
+        Ok(table.is_some_and(|t| t.contains_key(name)))
+    }
+
+    /// `val` with the type of the `current` value if it parses as one, a
+    /// string otherwise.
+    fn typed_value(current: Option<&toml_edit::Value>, val: &str) -> toml_edit::Value {
+        match current {
+            Some(toml_edit::Value::Integer(_)) => val.parse::<i64>().ok().map(Into::into),
+            Some(toml_edit::Value::Float(_)) => val.parse::<f64>().ok().map(Into::into),
+            Some(toml_edit::Value::Boolean(_)) => val.parse::<bool>().ok().map(Into::into),
+            Some(toml_edit::Value::Datetime(_)) => {
+                val.parse::<toml_edit::Datetime>().ok().map(Into::into)
+            }
+            _ => None,
+        }
+        .unwrap_or_else(|| val.into())
+    }
+
+    fn section_table<'a>(
+        doc: &'a mut DocumentMut,
+        section: Section,
+        create: bool,
+    ) -> Result<Option<&'a mut dyn TableLike>> {
+        let root = doc.as_table_mut();
+
+        let key = match section {
+            Section::Defaults => return Ok(Some(root)),
+            Section::Environment(key) => key,
+        };
+
+        // With an `environments` table, the environments are found in it
+        let parent: &mut dyn TableLike = match root.contains_key(ENVIRONMENTS_KEY) {
+            true => root[ENVIRONMENTS_KEY]
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow!("'{}' must be a table", ENVIRONMENTS_KEY))?,
+            false => root,
+        };
+
+        if !parent.contains_key(key) {
+            if !create {
+                return Ok(None);
+            }
+            parent.insert(key, Item::Table(Table::new()));
+        }
+
+        parent
+            .get_mut(key)
+            .unwrap()
+            .as_table_like_mut()
+            .map(Some)
+            .ok_or_else(|| anyhow!("'{}' is a variable, not an environment", key))
+    }
+}
+
+mod json {
+    //! JSON files are edited in place: only the text of the edited members
+    //! changes, new members follow the indentation of their neighbours.
+
+    use super::*;
+    use serde_json::Value;
+
+    pub fn set_variable(content: &str, section: Section, name: &str, val: &str) -> Result<String> {
+        check_name(section, name)?;
+
+        let root = Object::parse(content)?;
+        let unit = indent_unit(content, &root);
+        let value = serde_json::to_string(val)?;
+
+        let object = match section_object(&root, section)? {
+            Ok(object) => object,
+            // A new environment, with the variable in it
+            Err((parent, key)) => {
+                let value = match parent.member_indent(content, &unit) {
+                    Some(indent) => format!(
+                        "{{\n{}{}{}: {}\n{}}}",
+                        indent,
+                        unit,
+                        quote(name)?,
+                        value,
+                        indent
+                    ),
+                    None => format!("{{ {}: {} }}", quote(name)?, value),
+                };
+                return parent.insert(content, key, &value, &unit);
+            }
+        };
+
+        match object.member(name) {
+            Some(m) if m.object.is_some() => {
+                bail!("'{}' in {} is an object, not a variable", name, section)
+            }
+            Some(m) => Ok(format!(
+                "{}{}{}",
+                &content[..m.value_start],
+                typed_value(&content[m.value_start..m.value_end], val).unwrap_or(value),
+                &content[m.value_end..]
+            )),
+            None => object.insert(content, name, &value, &unit),
+        }
+    }
+
+    pub fn unset_variable(content: &str, section: Section, name: &str) -> Result<String> {
+        check_name(section, name)?;
+
+        let root = Object::parse(content)?;
+        let object = match section_object(&root, section)? {
+            Ok(object) => object,
+            Err(_) => bail!("{} does not exist", section),
+        };
+
+        let idx = match object.members.iter().position(|m| m.key == name) {
+            Some(idx) => idx,
+            None => bail!("variable '{}' is not defined in {}", name, section),
+        };
+        if object.members[idx].object.is_some() {
+            bail!("'{}' in {} is an object, not a variable", name, section);
+        }
+
+        // The member goes with the comma and whitespace that separate it
+        // from its neighbour
+        let members = &object.members;
+        let prev = idx.checked_sub(1).map(|i| &members[i]);
+        let (start, end) = match (prev, members.get(idx + 1)) {
+            (_, Some(next)) => (members[idx].start, next.start),
+            (Some(prev), None) => (prev.value_end, members[idx].value_end),
+            (None, None) => (object.open + 1, object.close),
+        };
+
+        Ok(format!("{}{}", &content[..start], &content[end..]))
+    }
+
+    pub fn get_variable(content: &str, section: Section, name: &str) -> Result<String> {
+        let root = Object::parse(content)?;
+        let object = match section_object(&root, section)? {
+            Ok(object) => object,
+            Err(_) => bail!("{} does not exist", section),
+        };
+
+        let member = match object.member(name) {
+            Some(m) => m,
+            None => bail!("variable '{}' is not defined in {}", name, section),
+        };
+
+        match serde_json::from_str(&content[member.value_start..member.value_end])? {
+            Value::String(s) => Ok(s),
+            Value::Number(n) => Ok(n.to_string()),
+            Value::Bool(b) => Ok(b.to_string()),
+            Value::Object(_) => {
+                bail!("'{}' in {} is an object, not a variable", name, section)
+            }
+            _ => bail!("'{}' in {} is not a single value", name, section),
+        }
+    }
+
+    pub fn contains_variable(content: &str, section: Section, name: &str) -> Result<bool> {
+        let root = Object::parse(content)?;
+        let object = section_object(&root, section)?;
+
+        Ok(object.is_ok_and(|o| o.member(name).is_some()))
+    }
+
+    /// `val` written as a number or boolean if the `current` value is one and
+    /// `val` parses as it.
+    fn typed_value(current: &str, val: &str) -> Option<String> {
+        match (
+            serde_json::from_str(current).ok()?,
+            serde_json::from_str(val).ok()?,
+        ) {
+            (Value::Number(_), Value::Number(n)) => Some(n.to_string()),
+            (Value::Bool(_), Value::Bool(b)) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The object of `section`, or the object it has to be added to and its
+    /// name if it doesn't exist yet.
+    fn section_object<'a>(
+        root: &'a Object,
+        section: Section<'a>,
+    ) -> Result<std::result::Result<&'a Object, (&'a Object, &'a str)>> {
+        let key = match section {
+            Section::Defaults => return Ok(Ok(root)),
+            Section::Environment(key) => key,
+        };
+
+        // With an `environments` object, the environments are found in it
+        let parent = match root.member(ENVIRONMENTS_KEY) {
+            Some(m) => m
+                .object
+                .as_ref()
+                .ok_or_else(|| anyhow!("'{}' must be an object", ENVIRONMENTS_KEY))?,
+            None => root,
+        };
+
+        match parent.member(key) {
+            Some(m) => m
+                .object
+                .as_ref()
+                .map(Ok)
+                .ok_or_else(|| anyhow!("'{}' is a variable, not an environment", key)),
+            None => Ok(Err((parent, key))),
+        }
+    }
+
+    fn quote(s: &str) -> Result<String> {
+        Ok(serde_json::to_string(s)?)
+    }
+
+    /// The indentation added by each level of objects, the one of the top
+    /// level members or else two spaces.
+    fn indent_unit(content: &str, root: &Object) -> String {
+        root.members
+            .first()
+            .filter(|m| starts_line(content, m.start))
+            .map(|m| line_indent(content, m.start))
+            .filter(|indent| !indent.is_empty())
+            .unwrap_or("  ")
+            .to_owned()
+    }
+
+    /// The whitespace at the start of the line of `pos`.
+    fn line_indent(content: &str, pos: usize) -> &str {
+        let line_start = content[..pos].rfind('\n').map_or(0, |i| i + 1);
+        let line = &content[line_start..];
+        &line[..line.len() - line.trim_start().len()]
+    }
+
+    /// Whether only whitespace comes before `pos` on its line.
+    fn starts_line(content: &str, pos: usize) -> bool {
+        let line_start = content[..pos].rfind('\n').map_or(0, |i| i + 1);
+        content[line_start..pos].trim().is_empty()
+    }
+
+    /// The position of the members of an object in the file.
+    struct Object {
+        /// Offset of the `{`
+        open: usize,
+        /// Offset of the `}`
+        close: usize,
+        members: Vec<Member>,
+    }
+
+    struct Member {
+        key: String,
+        /// Offset of the quote opening the key
+        start: usize,
+        value_start: usize,
+        value_end: usize,
+        /// The members of the value, if it is an object
+        object: Option<Object>,
+    }
+
+    impl Object {
+        fn parse(content: &str) -> Result<Object> {
+            // Validates the document, so the scanner can trust it
+            serde_json::from_str::<Value>(content)?;
+
+            let mut scanner = Scanner { content, pos: 0 };
+            scanner.skip_whitespace();
+            match scanner.value()? {
+                Some(object) => Ok(object),
+                None => bail!("the top level value is not an object"),
+            }
+        }
+
+        fn member(&self, key: &str) -> Option<&Member> {
+            self.members.iter().find(|m| m.key == key)
+        }
+
+        /// The indentation of the members, `None` if they are on the line
+        /// of the braces. An empty object gets one `unit` more than the line
+        /// of its opening brace.
+        fn member_indent(&self, content: &str, unit: &str) -> Option<String> {
+            match self.members.first() {
+                Some(m) if starts_line(content, m.start) => {
+                    Some(line_indent(content, m.start).to_owned())
+                }
+                Some(_) => None,
+                None => Some(format!("{}{}", line_indent(content, self.open), unit)),
+            }
+        }
+
+        /// `content` with the member `key` added at the end of the object.
+        fn insert(&self, content: &str, key: &str, value: &str, unit: &str) -> Result<String> {
+            let member = format!("{}: {}", quote(key)?, value);
+
+            let res = match (self.members.last(), self.member_indent(content, unit)) {
+                (Some(last), Some(indent)) => format!(
+                    "{},\n{}{}{}",
+                    &content[..last.value_end],
+                    indent,
+                    member,
+                    &content[last.value_end..]
+                ),
+                (Some(last), None) => format!(
+                    "{}, {}{}",
+                    &content[..last.value_end],
+                    member,
+                    &content[last.value_end..]
+                ),
+                (None, indent) => format!(
+                    "{}\n{}{}\n{}{}",
+                    &content[..self.open + 1],
+                    indent.unwrap_or_default(),
+                    member,
+                    line_indent(content, self.open),
+                    &content[self.close..]
+                ),
+            };
+
+            Ok(res)
+        }
+    }
+
+    /// Finds the objects and their members in a valid JSON document.
+    struct Scanner<'a> {
+        content: &'a str,
+        pos: usize,
+    }
+
+    impl Scanner<'_> {
+        fn peek(&self) -> Option<u8> {
+            self.content.as_bytes().get(self.pos).copied()
+        }
+
+        fn skip_whitespace(&mut self) {
+            while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+                self.pos += 1;
+            }
+        }
+
+        /// Skips the value at the current position, returning its members
+        /// if it is an object.
+        fn value(&mut self) -> Result<Option<Object>> {
+            match self.peek() {
+                Some(b'{') => return self.object().map(Some),
+                Some(b'[') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    while self.peek() != Some(b']') {
+                        self.value()?;
+                        self.skip_whitespace();
+                        if self.peek() == Some(b',') {
+                            self.pos += 1;
+                            self.skip_whitespace();
+                        }
+                    }
+                    self.pos += 1;
+                }
+                Some(b'"') => {
+                    self.string()?;
+                }
+                _ => {
+                    while !matches!(
+                        self.peek(),
+                        None | Some(b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')
+                    ) {
+                        self.pos += 1;
+                    }
+                }
+            }
+            Ok(None)
+        }
+
+        fn object(&mut self) -> Result<Object> {
+            let open = self.pos;
+            let mut members = Vec::new();
+
+            self.pos += 1;
+            self.skip_whitespace();
+            while self.peek() != Some(b'}') {
+                let start = self.pos;
+                let key = self.string()?;
+                self.skip_whitespace();
+                self.pos += 1; // the colon
+                self.skip_whitespace();
+                let value_start = self.pos;
+                let object = self.value()?;
+                members.push(Member {
+                    key,
+                    start,
+                    value_start,
+                    value_end: self.pos,
+                    object,
+                });
+                self.skip_whitespace();
+                if self.peek() == Some(b',') {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                }
+            }
+
+            let close = self.pos;
+            self.pos += 1;
+
+            Ok(Object {
+                open,
+                close,
+                members,
+            })
+        }
+
+        fn string(&mut self) -> Result<String> {
+            let start = self.pos;
+            self.pos += 1;
+            while let Some(c) = self.peek() {
+                self.pos += 1;
+                match c {
+                    b'\\' => self.pos += 1,
+                    b'"' => break,
+                    _ => (),
+                }
+            }
+            Ok(serde_json::from_str(&self.content[start..self.pos])?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML_CONTENT: &str = r#"# common variables
+FOO = "foo" # the foo
+BAR = "bar"
+
+# dev only
+[dev]
+BAR = "dev_bar"
+PORT = 8080
+"#;
+
+    const JSON_CONTENT: &str = r#"{
+  "FOO": "foo",
+  "BAR": "bar",
+  "dev": {
+    "BAR": "dev_bar",
+    "PORT": 8080
+  }
+}
+"#;
+
+    #[test]
+    fn test_toml_set_variable() {
+        let res = toml::set_variable(TOML_CONTENT, Section::Environment("dev"), "PORT", "9090");
+        assert_eq!(
+            res.unwrap(),
+            TOML_CONTENT.replace("PORT = 8080", "PORT = 9090")
+        );
+
+        let res = toml::set_variable(TOML_CONTENT, Section::Environment("dev"), "PORT", "http");
+        assert_eq!(
+            res.unwrap(),
+            TOML_CONTENT.replace("PORT = 8080", "PORT = \"http\"")
+        );
+
+        let res = toml::set_variable(TOML_CONTENT, Section::Defaults, "FOO", "new_foo");
+        assert_eq!(
+            res.unwrap(),
+            TOML_CONTENT.replace(r#"FOO = "foo""#, r#"FOO = "new_foo""#)
+        );
+
+        let res = toml::set_variable(TOML_CONTENT, Section::Defaults, "BAZ", "baz");
+        assert_eq!(
+            res.unwrap(),
+            TOML_CONTENT.replace("BAR = \"bar\"\n", "BAR = \"bar\"\nBAZ = \"baz\"\n")
+        );
+
+        let res = toml::set_variable(TOML_CONTENT, Section::Environment("qa"), "FOO", "qa");
+        assert_eq!(
+            res.unwrap(),
+            format!("{}\n[qa]\nFOO = \"qa\"\n", TOML_CONTENT)
+        );
+    }
+
+    #[test]
+    fn test_toml_set_variable_ambiguous() {
+        let err = |section, name| {
+            toml::set_variable(TOML_CONTENT, section, name, "x")
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            err(Section::Defaults, "dev"),
+            "'dev' in the defaults is a table, not a variable"
+        );
+        assert_eq!(
+            err(Section::Environment("FOO"), "X"),
+            "'FOO' is a variable, not an environment"
+        );
+        assert_eq!(
+            err(Section::Environment("dev"), "extends"),
+            "'extends' is reserved for environment inheritance"
+        );
+        assert_eq!(
+            err(Section::Environment("schema"), "FOO"),
+            "'schema' is reserved for the variable schema"
+        );
+    }
+
+    #[test]
+    fn test_toml_set_variable_environments() {
+        let content = "FOO = \"foo\"\n\n[environments.dev]\nFOO = \"dev_foo\"\n";
+
+        let res = toml::set_variable(content, Section::Environment("dev"), "BAR", "bar");
+        assert_eq!(res.unwrap(), format!("{}BAR = \"bar\"\n", content));
+
+        let res = toml::set_variable(content, Section::Environment("qa"), "FOO", "qa");
+        assert_eq!(
+            res.unwrap(),
+            format!("{}\n[environments.qa]\nFOO = \"qa\"\n", content)
+        );
+
+        let res = toml::set_variable(content, Section::Defaults, "environments", "x");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'environments' is reserved for the config layout"
+        );
+
+        let res = toml::set_variable(content, Section::Environment("arrays"), "join", ";");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'arrays' is reserved for the config layout"
+        );
+    }
+
+    #[test]
+    fn test_toml_unset_variable() {
+        let res = toml::unset_variable(TOML_CONTENT, Section::Environment("dev"), "PORT");
+        assert_eq!(res.unwrap(), TOML_CONTENT.replace("PORT = 8080\n", ""));
+
+        let res = toml::unset_variable(TOML_CONTENT, Section::Environment("dev"), "FOO");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "variable 'FOO' is not defined in environment 'dev'"
+        );
+
+        let res = toml::unset_variable(TOML_CONTENT, Section::Environment("qa"), "FOO");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "environment 'qa' does not exist"
+        );
+    }
+
+    #[test]
+    fn test_toml_get_variable() {
+        let get = |section, name| toml::get_variable(TOML_CONTENT, section, name);
+
+        assert_eq!(get(Section::Defaults, "FOO").unwrap(), "foo");
+        assert_eq!(get(Section::Environment("dev"), "PORT").unwrap(), "8080");
+        assert_eq!(
+            get(Section::Environment("dev"), "FOO")
+                .unwrap_err()
+                .to_string(),
+            "variable 'FOO' is not defined in environment 'dev'"
+        );
+    }
+
+    #[test]
+    fn test_json_get_variable() {
+        let get = |section, name| json::get_variable(JSON_CONTENT, section, name);
+
+        assert_eq!(get(Section::Environment("dev"), "BAR").unwrap(), "dev_bar");
+        assert_eq!(get(Section::Environment("dev"), "PORT").unwrap(), "8080");
+        assert_eq!(
+            get(Section::Defaults, "dev").unwrap_err().to_string(),
+            "'dev' in the defaults is an object, not a variable"
+        );
+    }
+
+    #[test]
+    fn test_json_set_variable() {
+        let res = json::set_variable(JSON_CONTENT, Section::Environment("dev"), "PORT", "9090");
+        assert_eq!(
+            res.unwrap(),
+            JSON_CONTENT.replace("\"PORT\": 8080", "\"PORT\": 9090")
+        );
+
+        let res = json::set_variable(JSON_CONTENT, Section::Environment("dev"), "PORT", "http");
+        assert_eq!(
+            res.unwrap(),
+            JSON_CONTENT.replace("\"PORT\": 8080", "\"PORT\": \"http\"")
+        );
+
+        let res = json::set_variable(JSON_CONTENT, Section::Defaults, "BAZ", "baz");
+        assert_eq!(
+            res.unwrap(),
+            JSON_CONTENT.replace("  }\n}", "  },\n  \"BAZ\": \"baz\"\n}")
+        );
+
+        let res = json::set_variable(JSON_CONTENT, Section::Defaults, "dev", "x");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'dev' in the defaults is an object, not a variable"
+        );
+    }
+
+    #[test]
+    fn test_json_unset_variable() {
+        let res = json::unset_variable(JSON_CONTENT, Section::Defaults, "FOO");
+        assert_eq!(
+            res.unwrap(),
+            JSON_CONTENT.replace("  \"FOO\": \"foo\",\n", "")
+        );
+
+        let res = json::unset_variable(JSON_CONTENT, Section::Environment("dev"), "PORT");
+        assert_eq!(
+            res.unwrap(),
+            JSON_CONTENT.replace(",\n    \"PORT\": 8080", "")
+        );
+
+        let res = json::unset_variable(JSON_CONTENT, Section::Environment("dev"), "FOO");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "variable 'FOO' is not defined in environment 'dev'"
+        );
+
+        let res =
+            json::unset_variable(r#"{"dev": {"FOO": 1}}"#, Section::Environment("dev"), "FOO");
+        assert_eq!(res.unwrap(), r#"{"dev": {}}"#);
+    }
+
+    #[test]
+    fn test_json_keeps_formatting() {
+        let content = "{\n    \"FOO\": \"foo\",   \"BAR\":[1, 2],\n\n    \"dev\": {\"A\": \"a\"},\n    \"qa\": {}\n}";
+
+        let res = json::set_variable(content, Section::Defaults, "FOO", "new");
+        assert_eq!(res.unwrap(), content.replace("\"foo\"", "\"new\""));
+
+        let res = json::set_variable(content, Section::Environment("dev"), "B", "b");
+        assert_eq!(
+            res.unwrap(),
+            content.replace("{\"A\": \"a\"}", "{\"A\": \"a\", \"B\": \"b\"}")
+        );
+
+        let res = json::set_variable(content, Section::Environment("qa"), "B", "b");
+        assert_eq!(
+            res.unwrap(),
+            content.replace("{}", "{\n        \"B\": \"b\"\n    }")
+        );
+
+        let res = json::set_variable(content, Section::Environment("prod"), "B", "b");
+        assert_eq!(
+            res.unwrap(),
+            content.replace(
+                "{}\n}",
+                "{},\n    \"prod\": {\n        \"B\": \"b\"\n    }\n}"
+            )
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Environments of other processes, to compare them with `ediff`.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// Reads the environment a running process was started with.
+#[cfg(target_os = "linux")]
+pub fn read_process(pid: u32) -> Result<Vec<(String, String)>> {
+    let path = format!("/proc/{}/environ", pid);
+
+    let content = std::fs::read(&path).with_context(|| {
+        format!(
+            "could not read the environment of process {} from `{}`",
+            pid, path
+        )
+    })?;
+
+    parse(&content, Path::new(&path))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_process(_pid: u32) -> Result<Vec<(String, String)>> {
+    bail!("reading the environment of another process is only supported on Linux")
+}
+
+/// Reads an environment saved with `env -0` or `env`.
+pub fn read_dump(path: &Path) -> Result<Vec<(String, String)>> {
+    let content =
+        std::fs::read(path).with_context(|| format!("could not read file `{}`", path.display()))?;
+
+    parse(&content, path)
+}
+
+/// Parses `KEY=value` entries separated by NUL bytes, like in
+/// `/proc/<pid>/environ` and the output of `env -0`, or by newlines if there
+/// are no NUL bytes.
+fn parse(content: &[u8], path: &Path) -> Result<Vec<(String, String)>> {
+    let separator = match content.contains(&0) {
+        true => b'\0',
+        false => b'\n',
+    };
+
+    let mut vars = Vec::new();
+
+    for (i, entry) in content.split(|b| *b == separator).enumerate() {
+        let entry = String::from_utf8_lossy(entry);
+        let entry = match separator {
+            b'\n' => entry.trim_end_matches('\r'),
+            _ => &entry,
+        };
+        if entry.is_empty() {
+            continue;
+        }
+
+        match entry.split_once('=') {
+            Some((key, value)) if !key.is_empty() => vars.push((key.to_owned(), value.to_owned())),
+            _ => bail!(
+                "invalid entry {} in `{}`, expected KEY=value (use `env -0` to save values containing newlines)",
+                i + 1,
+                path.display()
+            ),
+        }
+    }
+
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(content: &str) -> Result<Vec<(String, String)>> {
+        parse(content.as_bytes(), Path::new("env.dump"))
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_nul_separated() {
+        assert_eq!(
+            parse_str("FOO=foo\0MULTI=a\nb=c\0EMPTY=\0").unwrap(),
+            vars(&[("FOO", "foo"), ("MULTI", "a\nb=c"), ("EMPTY", "")])
+        );
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        assert_eq!(
+            parse_str("FOO=foo\r\nURL=http://x?a=b\n\n").unwrap(),
+            vars(&[("FOO", "foo"), ("URL", "http://x?a=b")])
+        );
+
+        assert_eq!(
+            parse_str("FOO=foo\ncontinued\n").unwrap_err().to_string(),
+            "invalid entry 2 in `env.dump`, expected KEY=value (use `env -0` to save values containing newlines)"
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Parser for `.env` files.
+//!
+//! Supported syntax:
+//!  * `KEY=value` pairs, one per line, optionally prefixed with `export`
+//!  * blank lines and lines starting with `#` are ignored
+//!  * unquoted values are trimmed and end at a ` #` inline comment
+//!  * single quoted values are taken literally and may span multiple lines
+//!  * double quoted values may span multiple lines and support the `\n`,
+//!    `\r`, `\t`, `\"`, `\\` and `\$` escapes
+//!
+//! A `.env.<key>` file provides the environment `<key>`, any other file the
+//! defaults. A directory provides the defaults from its `.env` file and an
+//! environment for every `.env.<key>` file next to it. A lone `.env` file
+//! has no environments, it is meant to be layered under `.env.<key>` files.
+
+use anyhow::{bail, Context, Result};
+use std::{collections::BTreeMap, path::Path};
+
+use super::ParsedConfig;
+use crate::parser::{ConfigParser, Value};
+use crate::schema::{self, Schema};
+use crate::{EnvVariable, EnvVariableMap};
+
+pub struct DotenvParser;
+
+impl ConfigParser for DotenvParser {
+    fn name(&self) -> &'static str {
+        "dotenv"
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        &["env"]
+    }
+
+    /// `.env` and `.env.<key>` files
+    fn matches_file_name(&self, path: &Path) -> bool {
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name == ".env" || name.starts_with(".env."),
+            None => false,
+        }
+    }
+
+    fn sniff(&self, content: &str) -> bool {
+        parse_variables(content).is_ok_and(|vars| !vars.is_empty())
+    }
+
+    fn parse_value(&self, content: &str) -> Result<Value> {
+        let variables = parse_variables(content)?;
+
+        Ok(Value::Table(
+            variables
+                .into_values()
+                .map(|var| (var.key, Value::String(var.value)))
+                .collect(),
+        ))
+    }
+
+    /// The variables are the environment named after the file, see
+    /// [`environment_key`], no key is special.
+    fn parse(&self, content: &str, file_path: &Path) -> Result<ParsedConfig> {
+        let variables = parse_variables(content)
+            .with_context(|| format!("failed to parse config file '{}'", file_path.display()))?;
+
+        let mut config = ParsedConfig {
+            schema: schema::with_schema_file(file_path, Schema::new())?,
+            ..Default::default()
+        };
+        match environment_key(file_path) {
+            Some(key) => {
+                config.overrides.insert(key.to_owned(), variables);
+            }
+            None => config.defaults = variables,
+        }
+
+        Ok(config)
+    }
+}
+
+/// The environment of a `.env.<key>` file, `None` for the files providing
+/// defaults.
+fn environment_key(path: &Path) -> Option<&str> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(|n| n.strip_prefix(".env."))
+        .filter(|key| !key.is_empty())
+}
+
+pub fn parse_dir(dir_path: &Path) -> Result<ParsedConfig> {
+    let mut config = ParsedConfig::default();
+
+    let entries = std::fs::read_dir(dir_path)
+        .with_context(|| format!("could not read directory `{}`", dir_path.display()))?;
+
+    for entry in entries {
+        let path = entry?.path();
+
+        let key = match environment_key(&path) {
+            Some(key) => Some(key.to_owned()),
+            None if path.file_name().is_some_and(|n| n == ".env") => None,
+            None => continue,
+        };
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read config file `{}`", path.display()))?;
+        let variables = parse_variables(&content)
+            .with_context(|| format!("failed to parse config file '{}'", path.display()))?;
+
+        match key {
+            Some(key) => {
+                config.overrides.insert(key, variables);
+            }
+            None => config.defaults = variables,
+        }
+    }
+
+    config.schema = schema::with_schema_file(dir_path, Schema::new())?;
+
+    Ok(config)
+}
+
+fn parse_variables(content: &str) -> Result<EnvVariableMap> {
+    let mut map = BTreeMap::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((idx, line)) = lines.next() {
+        let line_no = idx + 1;
+        let line = line.trim_start();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = match line.strip_prefix("export") {
+            Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+            _ => line,
+        };
+
+        let (key, raw_value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim_start()),
+            None => bail!("line {}: expected KEY=value", line_no),
+        };
+
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            bail!("line {}: invalid variable name '{}'", line_no, key);
+        }
+
+        let value = match raw_value.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                let mut quoted = raw_value[1..].to_owned();
+                loop {
+                    if let Some((value, rest)) = split_quoted(&quoted, quote) {
+                        let rest = rest.trim();
+                        if !rest.is_empty() && !rest.starts_with('#') {
+                            bail!(
+                                "line {}: unexpected characters after the closing quote",
+                                line_no
+                            );
+                        }
+                        break value;
+                    }
+                    match lines.next() {
+                        Some((_, next)) => {
+                            quoted.push('\n');
+                            quoted.push_str(next);
+                        }
+                        None => bail!("line {}: missing closing quote", line_no),
+                    }
+                }
+            }
+            _ => match raw_value.find(" #").or_else(|| raw_value.find("\t#")) {
+                Some(pos) => raw_value[..pos].trim_end().to_owned(),
+                None => raw_value.trim_end().to_owned(),
+            },
+        };
+
+        map.insert(key.to_owned(), EnvVariable::new(key, value));
+    }
+
+    Ok(map)
+}
+
+/// Splits a quoted value (without its opening quote) at the closing quote,
+/// returning the unescaped value and the rest of the input.
+pub(super) fn split_quoted(s: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((value, &s[idx + 1..])),
+            '\\' if quote == '"' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, '"')) => value.push('"'),
+                Some((_, '\\')) => value.push('\\'),
+                // A literal `$`, kept from interpolation
+                Some((_, '$')) => value.push_str("$$"),
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => value.push('\\'),
+            },
+            _ => value.push(c),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn value_of(content: &str, key: &str) -> String {
+        let config = DotenvParser.parse(content, Path::new(".env")).unwrap();
+        config.defaults[key].value.clone()
+    }
+
+    #[test]
+    fn test_dotenv_parser() -> Result<(), Box<dyn std::error::Error>> {
+        let content = r#"
+            # a comment
+            DEBUG=true
+            export FOO=foo
+            PORT = 8080  # inline comment
+        "#;
+
+        let mut defaults_expected: EnvVariableMap = BTreeMap::new();
+        defaults_expected.insert(
+            "DEBUG".to_owned(),
+            EnvVariable::new("DEBUG", "true".to_owned()),
+        );
+        defaults_expected.insert("FOO".to_owned(), EnvVariable::new("FOO", "foo".to_owned()));
+        defaults_expected.insert(
+            "PORT".to_owned(),
+            EnvVariable::new("PORT", "8080".to_owned()),
+        );
+
+        let config = DotenvParser.parse(content, Path::new(".env"))?;
+
+        assert_eq!(config.defaults, defaults_expected);
+        assert!(config.overrides.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dotenv_parser_quotes() {
+        assert_eq!(
+            value_of(r#"A='it is # "raw" \n'"#, "A"),
+            r#"it is # "raw" \n"#
+        );
+        assert_eq!(
+            value_of(r#"A="say \"hi\"\tC:\\ \x" # comment"#, "A"),
+            "say \"hi\"\tC:\\ \\x"
+        );
+        assert_eq!(value_of(r#"A="\$HOME""#, "A"), "$$HOME");
+        assert_eq!(value_of("A=\"\"", "A"), "");
+        assert_eq!(value_of("A=", "A"), "");
+        assert_eq!(value_of("A=a#b", "A"), "a#b");
+        assert_eq!(value_of("exported=1", "exported"), "1");
+    }
+
+    #[test]
+    fn test_dotenv_parser_multiline() {
+        let content = "A=\"first\nsecond\"\nB='one\n  two'\nC=c";
+
+        assert_eq!(value_of(content, "A"), "first\nsecond");
+        assert_eq!(value_of(content, "B"), "one\n  two");
+        assert_eq!(value_of(content, "C"), "c");
+    }
+
+    #[test]
+    fn test_dotenv_parser_errors() {
+        let err = |content: &str| {
+            format!(
+                "{:#}",
+                DotenvParser.parse(content, Path::new(".env")).unwrap_err()
+            )
+        };
+
+        assert_eq!(
+            err("A=1\nB"),
+            "failed to parse config file '.env': line 2: expected KEY=value"
+        );
+        assert_eq!(
+            err("A=\"open\nB=2"),
+            "failed to parse config file '.env': line 1: missing closing quote"
+        );
+        assert_eq!(
+            err("A='a' b"),
+            "failed to parse config file '.env': line 1: unexpected characters after the closing quote"
+        );
+        assert_eq!(
+            err("MY VAR=1"),
+            "failed to parse config file '.env': line 1: invalid variable name 'MY VAR'"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Parser for INI files.
+//!
+//! Supported syntax:
+//!  * `KEY = value` or `KEY: value` pairs, one per line
+//!  * the pairs before the first `[section]` are the defaults, every section
+//!    is the environment of the same name, sections given more than once are
+//!    merged
+//!  * dots in section names nest them, `[dev.database]` is the `database`
+//!    table of the `dev` environment
+//!  * blank lines and lines starting with `;` or `#` are ignored
+//!  * unquoted values are trimmed and end at a ` ;` or ` #` inline comment
+//!  * single quoted values are taken literally
+//!  * double quoted values support the `\n`, `\r`, `\t`, `\"`, `\\` and
+//!    `\$` escapes
+//!  * `extends` in a section takes a comma separated list of environments
+
+use anyhow::{bail, Context, Result};
+
+use super::dotenv::split_quoted;
+use super::{nested_table, set_value, EXTENDS_KEY};
+use crate::parser::{ConfigParser, Table, Value};
+
+pub struct IniParser;
+
+impl ConfigParser for IniParser {
+    fn name(&self) -> &'static str {
+        "ini"
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        &["ini", "cfg"]
+    }
+
+    /// Only files with sections, the others are left to the dotenv parser.
+    fn sniff(&self, content: &str) -> bool {
+        match self.parse_value(content) {
+            Ok(Value::Table(t)) => t.iter().any(|(_, v)| matches!(v, Value::Table(_))),
+            _ => false,
+        }
+    }
+
+    fn parse_value(&self, content: &str) -> Result<Value> {
+        let mut root = Table::new();
+        let mut section: Vec<String> = Vec::new();
+
+        for (idx, line) in content.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                section = match name.strip_suffix(']') {
+                    Some(name) => name.split('.').map(|p| p.trim().to_owned()).collect(),
+                    None => Vec::new(),
+                };
+                if section.iter().any(String::is_empty) || section.is_empty() {
+                    bail!("line {}: invalid section header '{}'", line_no, line);
+                }
+                let path: Vec<_> = section.iter().map(String::as_str).collect();
+                nested_table(&mut root, &path)
+                    .with_context(|| format!("line {}: invalid section '{}'", line_no, line))?;
+                continue;
+            }
+
+            let (key, raw_value) = match line.find(['=', ':']) {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim_start()),
+                None => bail!("line {}: expected KEY=value", line_no),
+            };
+
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                bail!("line {}: invalid variable name '{}'", line_no, key);
+            }
+
+            let value = parse_value(raw_value, line_no)?;
+            let value = match section.is_empty() {
+                false if key == EXTENDS_KEY && value.contains(',') => Value::Array(
+                    value
+                        .split(',')
+                        .map(|parent| Value::String(parent.trim().to_owned()))
+                        .collect(),
+                ),
+                _ => Value::String(value),
+            };
+
+            let path: Vec<_> = section.iter().map(String::as_str).collect();
+            let table = nested_table(&mut root, &path)?;
+            set_value(table, key, value).with_context(|| format!("line {}", line_no))?;
+        }
+
+        Ok(Value::Table(root))
+    }
+}
+
+fn parse_value(raw_value: &str, line_no: usize) -> Result<String> {
+    match raw_value.chars().next() {
+        Some(quote @ ('\'' | '"')) => match split_quoted(&raw_value[1..], quote) {
+            Some((value, rest)) => {
+                let rest = rest.trim();
+                if !rest.is_empty() && !rest.starts_with(';') && !rest.starts_with('#') {
+                    bail!(
+                        "line {}: unexpected characters after the closing quote",
+                        line_no
+                    );
+                }
+                Ok(value)
+            }
+            None => bail!("line {}: missing closing quote", line_no),
+        },
+        _ => {
+            let end = [" ;", "\t;", " #", "\t#"]
+                .iter()
+                .filter_map(|comment| raw_value.find(comment))
+                .min();
+            Ok(match end {
+                Some(pos) => raw_value[..pos].trim_end().to_owned(),
+                None => raw_value.trim_end().to_owned(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_ini_parser() -> Result<(), Box<dyn std::error::Error>> {
+        let content = r#"
+            ; shared by all environments
+            FOO = foo
+            URL: "http://localhost ; not a comment"
+
+            [dev]
+            FOO = dev_foo ; inline comment
+            RAW = 'C:\temp'
+
+            [prod-eu]
+            extends = prod, dev
+
+            [dev]
+            BAR = "tab\there"
+
+            [dev.database]
+            host = localhost
+        "#;
+
+        let config = IniParser.parse(content, Path::new("envi.ini"))?;
+
+        assert_eq!(config.defaults["FOO"].value, "foo");
+        assert_eq!(
+            config.defaults["URL"].value,
+            "http://localhost ; not a comment"
+        );
+        assert_eq!(config.overrides["dev"]["FOO"].value, "dev_foo");
+        assert_eq!(config.overrides["dev"]["RAW"].value, r"C:\temp");
+        assert_eq!(config.overrides["dev"]["BAR"].value, "tab\there");
+        assert_eq!(config.overrides["dev"]["DATABASE_HOST"].value, "localhost");
+        assert!(config.overrides["prod-eu"].is_empty());
+        assert_eq!(config.extends["prod-eu"], vec!["prod", "dev"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ini_parser_errors() {
+        let err = |content: &str| {
+            format!(
+                "{:#}",
+                IniParser.parse(content, Path::new("envi.ini")).unwrap_err()
+            )
+        };
+
+        assert_eq!(
+            err("[dev\nFOO=foo"),
+            "failed to parse config file 'envi.ini': line 1: invalid section header '[dev'"
+        );
+        assert_eq!(
+            err("FOO"),
+            "failed to parse config file 'envi.ini': line 1: expected KEY=value"
+        );
+        assert_eq!(
+            err("dev = 1\n[dev]"),
+            "failed to parse config file 'envi.ini': line 2: invalid section '[dev]': 'dev' is already a variable"
+        );
+        assert_eq!(
+            err("FOO = \"foo"),
+            "failed to parse config file 'envi.ini': line 1: missing closing quote"
+        );
+    }
+}
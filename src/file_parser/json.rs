@@ -1,70 +1,50 @@
-use anyhow::{Context, Result};
-use serde_json::Value;
-use std::{collections::BTreeMap, path::Path};
-
-use crate::{EnvVariable, EnvVariableMap};
-
-pub fn parse_config(
-    content: &str,
-    file_path: &Path,
-) -> Result<(EnvVariableMap, BTreeMap<String, EnvVariableMap>)> {
-    let config: Value = serde_json::from_str(content)
-        .with_context(|| format!("failed to parse config file '{}'", file_path.display()))?;
-
-    if let Value::Object(t) = config {
-        let defaults = defaults_from_object(&t);
-        let overrides = overrides_from_object(&t);
-        Ok((defaults, overrides))
-    } else {
-        let defaults = BTreeMap::new();
-        let overrides = BTreeMap::new();
-        Ok((defaults, overrides))
+use anyhow::Result;
+
+use crate::parser::{ConfigParser, Value};
+
+pub struct JsonParser;
+
+impl ConfigParser for JsonParser {
+    fn name(&self) -> &'static str {
+        "json"
     }
-}
 
-fn defaults_from_object(table: &serde_json::Map<String, Value>) -> EnvVariableMap {
-    table
-        .iter()
-        .filter_map(|(key, value)| to_env_varible(key, value))
-        .map(|item| (item.key.clone(), item))
-        .collect()
-}
+    fn extensions(&self) -> &[&'static str] {
+        &["json"]
+    }
 
-fn overrides_from_object(
-    table: &serde_json::Map<String, Value>,
-) -> BTreeMap<String, EnvVariableMap> {
-    let mut map = BTreeMap::new();
-
-    let child_objects: Vec<_> = table
-        .iter()
-        .filter_map(|(key, value)| match value {
-            serde_json::Value::Object(o) => Some((key.clone(), o)),
-            _ => None,
-        })
-        .collect();
-
-    for (key, obj) in child_objects.into_iter() {
-        map.insert(key, defaults_from_object(obj));
+    fn sniff(&self, content: &str) -> bool {
+        content.trim_start().starts_with('{')
+            && serde_json::from_str::<serde_json::Value>(content).is_ok()
     }
 
-    map
+    fn parse_value(&self, content: &str) -> Result<Value> {
+        Ok(to_value(serde_json::from_str(content)?))
+    }
 }
 
-fn to_env_varible(key: &str, value: &serde_json::Value) -> Option<EnvVariable> {
+fn to_value(value: serde_json::Value) -> Value {
     match value {
-        Value::String(s) => Some(EnvVariable::new(key, s.to_string())),
-        Value::Number(i) => Some(EnvVariable::new(key, i.to_string())),
-        Value::Bool(b) => Some(EnvVariable::new(key, b.to_string())),
-        _ => None,
+        serde_json::Value::String(s) => Value::String(s),
+        serde_json::Value::Number(n) => Value::Number(n.to_string()),
+        serde_json::Value::Bool(b) => Value::Bool(b),
+        serde_json::Value::Array(a) => Value::Array(a.into_iter().map(to_value).collect()),
+        serde_json::Value::Object(o) => {
+            Value::Table(o.into_iter().map(|(k, v)| (k, to_value(v))).collect())
+        }
+        serde_json::Value::Null => Value::Null,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::path::Path;
 
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::file_parser::SkippedValue;
+    use crate::{EnvVariable, EnvVariableMap};
 
     #[test]
     fn test_json_parser() -> Result<(), Box<dyn std::error::Error>> {
@@ -92,6 +72,10 @@ mod tests {
             "PORT".to_owned(),
             EnvVariable::new("PORT", "8080".to_owned()),
         );
+        defaults_expected.insert(
+            "bars".to_owned(),
+            EnvVariable::new("bars", "1,2,3".to_owned()),
+        );
 
         let mut overrides_expected: BTreeMap<String, EnvVariableMap> = BTreeMap::new();
         let mut test_env_var_map: EnvVariableMap = BTreeMap::new();
@@ -109,11 +93,61 @@ mod tests {
         );
         overrides_expected.insert("test".to_owned(), test_env_var_map);
 
-        let (defaults, overrides) = super::parse_config(content, Path::new("testfile.json"))?;
+        let config = JsonParser.parse(content, Path::new("testfile.json"))?;
+
+        assert_eq!(config.defaults, defaults_expected);
+        assert_eq!(config.overrides, overrides_expected);
+        assert!(config.extends.is_empty());
+        assert_eq!(config.skipped, vec![SkippedValue::new(None, "aaa", "null")]);
 
-        assert_eq!(defaults, defaults_expected);
-        assert_eq!(overrides, overrides_expected);
+        Ok(())
+    }
 
-        return Ok(());
+    #[test]
+    fn test_json_parser_extends() -> Result<(), Box<dyn std::error::Error>> {
+        let content = r#"
+        {
+            "FOO": "foo",
+            "prod": {
+              "FOO": "prod_foo"
+            },
+            "staging": {
+              "extends": "prod",
+              "BAR": "staging_bar"
+            },
+            "prod-eu": {
+              "extends": ["prod", "staging"]
+            }
+        }"#;
+
+        let config = JsonParser.parse(content, Path::new("testfile.json"))?;
+
+        let mut extends_expected: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        extends_expected.insert("staging".to_owned(), vec!["prod".to_owned()]);
+        extends_expected.insert(
+            "prod-eu".to_owned(),
+            vec!["prod".to_owned(), "staging".to_owned()],
+        );
+
+        assert_eq!(config.extends, extends_expected);
+        assert_eq!(config.overrides["prod-eu"], BTreeMap::new());
+        assert_eq!(
+            config.overrides["staging"].keys().collect::<Vec<_>>(),
+            vec!["BAR"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_parser_invalid_extends() {
+        let content = r#"{ "prod": { "extends": 1 } }"#;
+
+        let res = JsonParser.parse(content, Path::new("testfile.json"));
+
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'prod.extends' must be a string or a list of strings"
+        );
     }
 }
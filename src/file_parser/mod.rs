@@ -1,3 +1,94 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+use crate::parser::{Table, Value};
+use crate::schema::Schema;
+use crate::EnvVariableMap;
+
+pub mod dotenv;
+pub mod ini;
 pub mod json;
+pub mod properties;
 pub mod toml;
 pub mod yaml;
+
+/// Name of the key used inside an environment table to inherit from other environments.
+pub const EXTENDS_KEY: &str = "extends";
+
+/// Name of the top level table describing the variables, see [`crate::schema`].
+pub const SCHEMA_KEY: &str = "schema";
+
+/// Name of the top level table holding the environments. Without it the
+/// tables at the top level are the environments.
+pub const ENVIRONMENTS_KEY: &str = "environments";
+
+/// Name of the top level table configuring how nested tables are flattened,
+/// see [`crate::parser::Flatten`].
+pub const FLATTEN_KEY: &str = "flatten";
+
+/// Name of the top level table configuring how arrays are joined, see
+/// [`crate::parser::Arrays`].
+pub const ARRAYS_KEY: &str = "arrays";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedConfig {
+    pub defaults: EnvVariableMap,
+    pub overrides: BTreeMap<String, EnvVariableMap>,
+    pub extends: BTreeMap<String, Vec<String>>,
+    /// Values that can't be turned into environment variables
+    pub skipped: Vec<SkippedValue>,
+    pub schema: Schema,
+}
+
+/// A value that was dropped while parsing, like a null.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedValue {
+    /// The environment the value is defined in, `None` for the defaults
+    pub environment: Option<String>,
+    pub name: String,
+    /// The type of the value, e.g. `null`
+    pub value_type: &'static str,
+}
+
+impl SkippedValue {
+    pub fn new(environment: Option<&str>, name: &str, value_type: &'static str) -> Self {
+        SkippedValue {
+            environment: environment.map(str::to_owned),
+            name: name.to_owned(),
+            value_type,
+        }
+    }
+}
+
+/// The table at `path` inside `table`, creating the missing ones, for the
+/// formats where nesting is written with dotted names.
+pub(crate) fn nested_table<'a>(table: &'a mut Table, path: &[&str]) -> Result<&'a mut Table> {
+    let (first, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(table),
+    };
+
+    let pos = match table.iter().position(|(k, _)| k == first) {
+        Some(pos) => pos,
+        None => {
+            table.push((first.to_string(), Value::Table(Table::new())));
+            table.len() - 1
+        }
+    };
+
+    match &mut table[pos].1 {
+        Value::Table(t) => nested_table(t, rest),
+        _ => bail!("'{}' is already a variable", first),
+    }
+}
+
+/// Sets `key` in `table`, later values replacing earlier ones.
+pub(crate) fn set_value(table: &mut Table, key: &str, value: Value) -> Result<()> {
+    match table.iter_mut().find(|(k, _)| k == key) {
+        Some((_, Value::Table(_))) => bail!("'{}' is already a table", key),
+        Some((_, v)) => *v = value,
+        None => table.push((key.to_owned(), value)),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,225 @@
+//! Parser for Java `.properties` files.
+//!
+//! Supported syntax, like `java.util.Properties`:
+//!  * `key=value`, `key: value` or `key value` pairs, the whitespace around
+//!    the separator is ignored and a key on its own has an empty value
+//!  * blank lines and lines starting with `#` or `!` are ignored
+//!  * a line ending with an odd number of backslashes continues on the next
+//!    line, whose leading whitespace is dropped
+//!  * the `\t`, `\n`, `\r`, `\f` and `\uXXXX` escapes, any other escaped
+//!    character stands for itself, e.g. `\=`, `\:`, `\ ` or `\\`
+//!
+//! Keys without a dot are the defaults, `<env>.<NAME>` keys set `NAME` in
+//! the environment `<env>`. Further dots nest tables like in the other
+//! formats, `dev.database.host` is `host` in the `database` table of `dev`.
+//! `<env>.extends` takes a comma separated list of environments.
+
+use anyhow::{bail, Context, Result};
+
+use super::{nested_table, set_value, EXTENDS_KEY};
+use crate::parser::{ConfigParser, Table, Value};
+
+pub struct PropertiesParser;
+
+impl ConfigParser for PropertiesParser {
+    fn name(&self) -> &'static str {
+        "properties"
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        &["properties"]
+    }
+
+    fn parse_value(&self, content: &str) -> Result<Value> {
+        let mut root = Table::new();
+
+        for (line_no, line) in logical_lines(content) {
+            let (key, value) = split_pair(&line);
+            let key = unescape(key, line_no)?;
+            let value = unescape(value, line_no)?;
+
+            if key.is_empty() {
+                bail!("line {}: missing key", line_no);
+            }
+
+            let path: Vec<_> = key.split('.').collect();
+            let (name, path) = path.split_last().unwrap();
+            if name.is_empty() || path.iter().any(|p| p.is_empty()) {
+                bail!("line {}: invalid key '{}'", line_no, key);
+            }
+
+            let value = match path.is_empty() {
+                false if *name == EXTENDS_KEY => Value::Array(
+                    value
+                        .split(',')
+                        .map(|parent| Value::String(parent.trim().to_owned()))
+                        .collect(),
+                ),
+                _ => Value::String(value),
+            };
+
+            let table =
+                nested_table(&mut root, path).with_context(|| format!("line {}", line_no))?;
+            set_value(table, name, value).with_context(|| format!("line {}", line_no))?;
+        }
+
+        Ok(Value::Table(root))
+    }
+}
+
+/// The lines of `content` with their continuations joined, skipping blank
+/// and comment lines, along with the number of their first line.
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim_start();
+
+        let (line_no, mut logical) = match current.take() {
+            Some(current) => current,
+            None if line.is_empty() || line.starts_with('#') || line.starts_with('!') => continue,
+            None => (idx + 1, String::new()),
+        };
+
+        let backslashes = line.len() - line.trim_end_matches('\\').len();
+        match backslashes % 2 {
+            1 => {
+                logical.push_str(&line[..line.len() - 1]);
+                current = Some((line_no, logical));
+            }
+            _ => {
+                logical.push_str(line);
+                lines.push((line_no, logical));
+            }
+        }
+    }
+
+    lines.extend(current);
+    lines
+}
+
+/// Splits a logical line at the first unescaped `=`, `:` or whitespace.
+fn split_pair(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '=' | ':' => return (&line[..idx], line[idx + 1..].trim_start()),
+            c if c.is_whitespace() => {
+                let rest = line[idx..].trim_start();
+                let rest = match rest.strip_prefix(['=', ':']) {
+                    Some(rest) => rest.trim_start(),
+                    None => rest,
+                };
+                return (&line[..idx], rest);
+            }
+            _ => (),
+        }
+    }
+
+    (line, "")
+}
+
+fn unescape(s: &str, line_no: usize) -> Result<String> {
+    let mut res = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => res.push('\t'),
+            Some('n') => res.push('\n'),
+            Some('r') => res.push('\r'),
+            Some('f') => res.push('\u{c}'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(c) if hex.len() == 4 => res.push(c),
+                    _ => bail!("line {}: invalid escape '\\u{}'", line_no, hex),
+                }
+            }
+            Some(other) => res.push(other),
+            None => (),
+        }
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_properties_parser() -> Result<(), Box<dyn std::error::Error>> {
+        let content = r#"
+            # shared by all environments
+            FOO = foo
+            URL: http://localhost:8080/path
+            GREETING Hello \
+                     World
+            EMPTY
+            ! comment
+            dev.FOO=dev_foo
+            dev.PATH=C:\\temp\tx \u00e9
+            dev.KEY\=WITH\:SEPARATORS=value
+            dev.database.host=localhost
+            prod-eu.extends = prod, dev
+        "#;
+
+        let config = PropertiesParser.parse(content, Path::new("envi.properties"))?;
+
+        assert_eq!(config.defaults["FOO"].value, "foo");
+        assert_eq!(config.defaults["URL"].value, "http://localhost:8080/path");
+        assert_eq!(config.defaults["GREETING"].value, "Hello World");
+        assert_eq!(config.defaults["EMPTY"].value, "");
+        assert_eq!(config.overrides["dev"]["FOO"].value, "dev_foo");
+        assert_eq!(config.overrides["dev"]["PATH"].value, "C:\\temp\tx é");
+        assert_eq!(
+            config.overrides["dev"]["KEY=WITH:SEPARATORS"].value,
+            "value"
+        );
+        assert_eq!(config.overrides["dev"]["DATABASE_HOST"].value, "localhost");
+        assert_eq!(config.extends["prod-eu"], vec!["prod", "dev"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_properties_parser_errors() {
+        let err = |content: &str| {
+            format!(
+                "{:#}",
+                PropertiesParser
+                    .parse(content, Path::new("envi.properties"))
+                    .unwrap_err()
+            )
+        };
+
+        assert_eq!(
+            err("FOO=foo\n.BAR=bar"),
+            "failed to parse config file 'envi.properties': line 2: invalid key '.BAR'"
+        );
+        assert_eq!(
+            err("FOO=\\u12"),
+            "failed to parse config file 'envi.properties': line 1: invalid escape '\\u12'"
+        );
+        assert_eq!(
+            err("dev=1\ndev.FOO=foo"),
+            "failed to parse config file 'envi.properties': line 2: 'dev' is already a variable"
+        );
+        assert_eq!(
+            err("dev.FOO=foo\ndev=1"),
+            "failed to parse config file 'envi.properties': line 2: 'dev' is already a table"
+        );
+    }
+}
@@ -1,73 +1,52 @@
-use anyhow::{Context, Result};
-use std::{collections::BTreeMap, path::Path};
-
-use crate::{EnvVariable, EnvVariableMap};
-
-pub fn parse_config(
-    content: &str,
-    file_path: &Path,
-) -> Result<(EnvVariableMap, BTreeMap<String, EnvVariableMap>)> {
-    let config: toml::Value = toml::from_str(content)
-        .with_context(|| format!("failed to parse config file '{}'", file_path.display()))?;
-
-    match config.as_table() {
-        Some(t) => {
-            let defaults = defaults_from_table(t);
-            let overrides = overrides_from_table(t);
-            Ok((defaults, overrides))
-        }
-        None => {
-            let defaults = BTreeMap::new();
-            let overrides = BTreeMap::new();
-            Ok((defaults, overrides))
-        }
-    }
-}
+use anyhow::Result;
 
-fn defaults_from_table(table: &toml::value::Table) -> EnvVariableMap {
-    table
-        .iter()
-        .filter_map(|(key, value)| to_env_varible(key, value))
-        .map(|item| (item.key.clone(), item))
-        .collect()
-}
+use crate::parser::{ConfigParser, Value};
 
-fn overrides_from_table(table: &toml::value::Table) -> BTreeMap<String, EnvVariableMap> {
-    let mut map = BTreeMap::new();
+pub struct TomlParser;
 
-    let sub_tables: Vec<_> = table
-        .iter()
-        .filter_map(|(key, value)| match value {
-            toml::Value::Table(t) => Some((key.clone(), t)),
-            _ => None,
-        })
-        .collect();
+impl ConfigParser for TomlParser {
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        &["toml"]
+    }
 
-    for (key, tbl) in sub_tables.into_iter() {
-        map.insert(key, defaults_from_table(tbl));
+    fn sniff(&self, content: &str) -> bool {
+        match toml::from_str::<toml::Value>(content) {
+            Ok(toml::Value::Table(t)) => !t.is_empty(),
+            _ => false,
+        }
     }
 
-    map
+    fn parse_value(&self, content: &str) -> Result<Value> {
+        Ok(to_value(toml::from_str(content)?))
+    }
 }
 
-fn to_env_varible(key: &str, value: &toml::value::Value) -> Option<EnvVariable> {
+fn to_value(value: toml::Value) -> Value {
     match value {
-        toml::Value::String(s) => Some(EnvVariable::new(key, s.to_string())),
-        toml::Value::Integer(i) => Some(EnvVariable::new(key, i.to_string())),
-        toml::Value::Float(f) => Some(EnvVariable::new(key, f.to_string())),
-        toml::Value::Boolean(b) => Some(EnvVariable::new(key, b.to_string())),
-        toml::Value::Datetime(d) => Some(EnvVariable::new(key, d.to_string())),
-        toml::Value::Table(_) => None,
-        toml::Value::Array(_) => None,
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::Number(i.to_string()),
+        toml::Value::Float(f) => Value::Number(f.to_string()),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(to_value).collect()),
+        toml::Value::Table(t) => {
+            Value::Table(t.into_iter().map(|(k, v)| (k, to_value(v))).collect())
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::path::Path;
 
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::{EnvVariable, EnvVariableMap};
 
     #[test]
     fn test_json_parser() -> Result<(), Box<dyn std::error::Error>> {
@@ -93,6 +72,10 @@ mod tests {
             "PORT".to_owned(),
             EnvVariable::new("PORT", "8080".to_owned()),
         );
+        defaults_expected.insert(
+            "bars".to_owned(),
+            EnvVariable::new("bars", "1,2,3".to_owned()),
+        );
 
         let mut overrides_expected: BTreeMap<String, EnvVariableMap> = BTreeMap::new();
         let mut test_env_var_map: EnvVariableMap = BTreeMap::new();
@@ -110,11 +93,63 @@ mod tests {
         );
         overrides_expected.insert("test".to_owned(), test_env_var_map);
 
-        let (defaults, overrides) = super::parse_config(content, Path::new("testfile.json"))?;
+        let config = TomlParser.parse(content, Path::new("testfile.json"))?;
+
+        assert_eq!(config.defaults, defaults_expected);
+        assert_eq!(config.overrides, overrides_expected);
+        assert!(config.extends.is_empty());
+        assert!(config.skipped.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_parser_extends() -> Result<(), Box<dyn std::error::Error>> {
+        let content = r#"
+            FOO = "foo"
+
+            [prod]
+            FOO = "prod_foo"
+
+            [staging]
+            extends = "prod"
+            BAR = "staging_bar"
+
+            [prod-eu]
+            extends = ["prod", "staging"]
+        "#;
+
+        let config = TomlParser.parse(content, Path::new("testfile.toml"))?;
+
+        let mut extends_expected: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        extends_expected.insert("staging".to_owned(), vec!["prod".to_owned()]);
+        extends_expected.insert(
+            "prod-eu".to_owned(),
+            vec!["prod".to_owned(), "staging".to_owned()],
+        );
+
+        assert_eq!(config.extends, extends_expected);
+        assert_eq!(config.overrides["prod-eu"], BTreeMap::new());
+        assert_eq!(
+            config.overrides["staging"].keys().collect::<Vec<_>>(),
+            vec!["BAR"]
+        );
 
-        assert_eq!(defaults, defaults_expected);
-        assert_eq!(overrides, overrides_expected);
+        Ok(())
+    }
 
-        return Ok(());
+    #[test]
+    fn test_toml_parser_invalid_extends() {
+        let content = r#"
+            [prod]
+            extends = 1
+        "#;
+
+        let res = TomlParser.parse(content, Path::new("testfile.toml"));
+
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'prod.extends' must be a string or a list of strings"
+        );
     }
 }
@@ -1,76 +1,64 @@
-use anyhow::{Context, Result};
-use std::{collections::BTreeMap, path::Path};
-
-use serde_yaml::mapping;
-use serde_yaml::Value;
-
-use crate::{EnvVariable, EnvVariableMap};
-
-pub fn parse_config(
-    content: &str,
-    file_path: &Path,
-) -> Result<(EnvVariableMap, BTreeMap<String, EnvVariableMap>)> {
-    let config: Value = serde_yaml::from_str(content)
-        .with_context(|| format!("failed to parse config file '{}'", file_path.display()))?;
-
-    if let Value::Mapping(m) = config {
-        let defaults = defaults_from_mapping(&m);
-        let overrides = overrides_from_mapping(&m);
-        Ok((defaults, overrides))
-    } else {
-        let defaults = BTreeMap::new();
-        let overrides = BTreeMap::new();
-        Ok((defaults, overrides))
-    }
-}
+use anyhow::Result;
 
-fn defaults_from_mapping(mapping: &mapping::Mapping) -> EnvVariableMap {
-    mapping
-        .iter()
-        .filter_map(|(key, value)| to_env_varible(key, value))
-        .map(|item| (item.key.clone(), item))
-        .collect()
-}
+use crate::parser::{ConfigParser, Value};
 
-fn overrides_from_mapping(mapping: &mapping::Mapping) -> BTreeMap<String, EnvVariableMap> {
-    let mut map = BTreeMap::new();
+pub struct YamlParser;
 
-    let child_mappings: Vec<_> = mapping
-        .iter()
-        .filter_map(|(key, value)| match value {
-            Value::Mapping(o) => Some((key.clone(), o)),
-            _ => None,
-        })
-        .collect();
+impl ConfigParser for YamlParser {
+    fn name(&self) -> &'static str {
+        "yaml"
+    }
 
-    for (key_value, m) in child_mappings.into_iter() {
-        if let Value::String(key) = key_value {
-            map.insert(key, defaults_from_mapping(m));
+    fn extensions(&self) -> &[&'static str] {
+        &["yaml", "yml"]
+    }
+
+    fn sniff(&self, content: &str) -> bool {
+        match serde_yaml::from_str::<serde_yaml::Value>(content) {
+            Ok(serde_yaml::Value::Mapping(m)) => !m.is_empty(),
+            _ => false,
         }
     }
 
-    map
-}
+    fn parse_value(&self, content: &str) -> Result<Value> {
+        Ok(to_value(serde_yaml::from_str(content)?))
+    }
 
-fn to_env_varible(key_value: &Value, value: &Value) -> Option<EnvVariable> {
-    if let Value::String(key) = key_value {
+    fn type_name(&self, value: &Value) -> &'static str {
         match value {
-            Value::String(s) => Some(EnvVariable::new(key, s.to_string())),
-            Value::Number(i) => Some(EnvVariable::new(key, i.to_string())),
-            Value::Bool(b) => Some(EnvVariable::new(key, b.to_string())),
-            _ => None,
+            Value::Array(_) => "sequence",
+            _ => value.type_name(),
         }
-    } else {
-        None
+    }
+}
+
+/// Entries whose key is not a string are dropped.
+fn to_value(value: serde_yaml::Value) -> Value {
+    match value {
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Number(n) => Value::Number(n.to_string()),
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Sequence(a) => Value::Array(a.into_iter().map(to_value).collect()),
+        serde_yaml::Value::Mapping(m) => Value::Table(
+            m.into_iter()
+                .filter_map(|(k, v)| match k {
+                    serde_yaml::Value::String(k) => Some((k, to_value(v))),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        serde_yaml::Value::Null => Value::Null,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::path::Path;
 
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::{EnvVariable, EnvVariableMap};
 
     #[test]
     fn test_json_parser() -> Result<(), Box<dyn std::error::Error>> {
@@ -98,6 +86,10 @@ mod tests {
             "PORT".to_owned(),
             EnvVariable::new("PORT", "8080".to_owned()),
         );
+        defaults_expected.insert(
+            "bars".to_owned(),
+            EnvVariable::new("bars", "1,2,3".to_owned()),
+        );
 
         let mut overrides_expected: BTreeMap<String, EnvVariableMap> = BTreeMap::new();
         let mut test_env_var_map: EnvVariableMap = BTreeMap::new();
@@ -115,11 +107,62 @@ mod tests {
         );
         overrides_expected.insert("test".to_owned(), test_env_var_map);
 
-        let (defaults, overrides) = super::parse_config(content, Path::new("testfile.json"))?;
+        let config = YamlParser.parse(content, Path::new("testfile.json"))?;
 
-        assert_eq!(defaults, defaults_expected);
-        assert_eq!(overrides, overrides_expected);
+        assert_eq!(config.defaults, defaults_expected);
+        assert_eq!(config.overrides, overrides_expected);
+        assert!(config.extends.is_empty());
+        assert!(config.skipped.is_empty());
 
-        return Ok(());
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_parser_extends() -> Result<(), Box<dyn std::error::Error>> {
+        let content = r#"
+            FOO: foo
+            prod:
+              FOO: prod_foo
+            staging:
+              extends: prod
+              BAR: staging_bar
+            prod-eu:
+              extends:
+                - prod
+                - staging
+        "#;
+
+        let config = YamlParser.parse(content, Path::new("testfile.yaml"))?;
+
+        let mut extends_expected: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        extends_expected.insert("staging".to_owned(), vec!["prod".to_owned()]);
+        extends_expected.insert(
+            "prod-eu".to_owned(),
+            vec!["prod".to_owned(), "staging".to_owned()],
+        );
+
+        assert_eq!(config.extends, extends_expected);
+        assert_eq!(config.overrides["prod-eu"], BTreeMap::new());
+        assert_eq!(
+            config.overrides["staging"].keys().collect::<Vec<_>>(),
+            vec!["BAR"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_parser_invalid_extends() {
+        let content = r#"
+            prod:
+              extends: 1
+        "#;
+
+        let res = YamlParser.parse(content, Path::new("testfile.yaml"));
+
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'prod.extends' must be a string or a list of strings"
+        );
     }
 }
@@ -0,0 +1,391 @@
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::EnvVariable;
+
+/// Turns a list of environment variables into lines of text, for example
+/// shell commands that set them. Fails if a value can't be represented in
+/// the target format.
+pub trait Formatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>>;
+}
+
+/// The shells `show` can generate commands for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    /// Plain `KEY=value` lines with POSIX shell quoting
+    Env,
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Nushell,
+    Csh,
+}
+
+impl Shell {
+    pub const NAMES: &'static [&'static str] = &[
+        "env",
+        "bash",
+        "zsh",
+        "fish",
+        "powershell",
+        "pwsh",
+        "nushell",
+        "nu",
+        "csh",
+        "tcsh",
+    ];
+
+    pub fn formatter(&self) -> Box<dyn Formatter> {
+        match self {
+            Shell::Env => Box::new(PosixFormatter { export: false }),
+            Shell::Bash | Shell::Zsh => Box::new(PosixFormatter { export: true }),
+            Shell::Fish => Box::new(FishFormatter),
+            Shell::PowerShell => Box::new(PowerShellFormatter),
+            Shell::Nushell => Box::new(NushellFormatter),
+            Shell::Csh => Box::new(CshFormatter),
+        }
+    }
+}
+
+impl FromStr for Shell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "env" => Ok(Shell::Env),
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            "powershell" | "pwsh" => Ok(Shell::PowerShell),
+            "nushell" | "nu" => Ok(Shell::Nushell),
+            "csh" | "tcsh" => Ok(Shell::Csh),
+            _ => bail!("unsupported shell: {}", s),
+        }
+    }
+}
+
+/// `KEY=value`, or `export KEY=value`, for POSIX compatible shells.
+pub struct PosixFormatter {
+    pub export: bool,
+}
+
+impl Formatter for PosixFormatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>> {
+        let prefix = if self.export { "export " } else { "" };
+
+        Ok(variables
+            .iter()
+            .map(|v| {
+                format!(
+                    "{}{}={}",
+                    prefix,
+                    v.key,
+                    shell_escape::unix::escape(Cow::Borrowed(&v.value))
+                )
+            })
+            .collect())
+    }
+}
+
+/// `set -gx KEY 'value'` for fish, where only `\` and `'` need escaping
+/// inside single quotes.
+pub struct FishFormatter;
+
+impl Formatter for FishFormatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>> {
+        Ok(variables
+            .iter()
+            .map(|v| format!("set -gx {} {}", v.key, fish_quote(&v.value)))
+            .collect())
+    }
+}
+
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// `$env:KEY = 'value'` for PowerShell, where quotes are escaped by doubling
+/// them. PowerShell also treats the typographic single quotes as quotes.
+pub struct PowerShellFormatter;
+
+impl Formatter for PowerShellFormatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>> {
+        Ok(variables
+            .iter()
+            .map(|v| {
+                let name = if v.key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    format!("$env:{}", v.key)
+                } else {
+                    format!("${{env:{}}}", v.key.replace('`', "``").replace('}', "`}"))
+                };
+                format!("{} = {}", name, powershell_quote(&v.value))
+            })
+            .collect())
+    }
+}
+
+fn powershell_quote(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('\'');
+    for c in s.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+            res.push(c);
+        }
+        res.push(c);
+    }
+    res.push('\'');
+    res
+}
+
+/// A single `load-env { "KEY": "value", ... }` record for nushell.
+pub struct NushellFormatter;
+
+impl Formatter for NushellFormatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>> {
+        let entries: Vec<_> = variables
+            .iter()
+            .map(|v| format!("{}: {}", nushell_quote(&v.key), nushell_quote(&v.value)))
+            .collect();
+
+        Ok(vec![format!("load-env {{{}}}", entries.join(", "))])
+    }
+}
+
+fn nushell_quote(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// `setenv KEY 'value'` for csh and tcsh. History expansion (`!`) and
+/// newlines have to be escaped even inside single quotes.
+pub struct CshFormatter;
+
+impl Formatter for CshFormatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>> {
+        Ok(variables
+            .iter()
+            .map(|v| format!("setenv {} {}", v.key, csh_quote(&v.value)))
+            .collect())
+    }
+}
+
+fn csh_quote(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' => res.push_str("'\\''"),
+            '!' => res.push_str("\\!"),
+            '\n' => res.push_str("\\\n"),
+            _ => res.push(c),
+        }
+    }
+    res.push('\'');
+    res
+}
+
+/// `KEY=value` lines for `.env` files. Values containing anything other than
+/// a small set of safe characters are double quoted, with `\`, `"`, `$` and
+/// control characters escaped.
+pub struct DotenvFormatter;
+
+impl Formatter for DotenvFormatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>> {
+        Ok(variables
+            .iter()
+            .map(|v| format!("{}={}", v.key, dotenv_quote(&v.value)))
+            .collect())
+    }
+}
+
+fn dotenv_quote(s: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:@,+%".contains(c);
+
+    if s.chars().all(is_safe) {
+        return Cow::Borrowed(s);
+    }
+
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '$' => res.push_str("\\$"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+    Cow::Owned(res)
+}
+
+/// `KEY=value` lines for `docker run --env-file`. Docker takes the values
+/// literally, so no quoting is applied and multi-line values are rejected.
+pub struct DockerFormatter;
+
+impl Formatter for DockerFormatter {
+    fn format(&self, variables: &[&EnvVariable]) -> Result<Vec<String>> {
+        variables
+            .iter()
+            .map(|v| {
+                if v.value.contains(['\n', '\r']) {
+                    bail!(
+                        "the value of '{}' spans multiple lines, which docker env files do not support",
+                        v.key
+                    );
+                }
+                Ok(format!("{}={}", v.key, v.value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::file_parser::dotenv::DotenvParser;
+    use crate::interpolate::interpolate;
+    use crate::parser::ConfigParser;
+
+    fn format(shell: Shell, vars: &[(&str, &str)]) -> Vec<String> {
+        let vars: Vec<_> = vars
+            .iter()
+            .map(|(k, v)| EnvVariable::new(k, v.to_string()))
+            .collect();
+        let refs: Vec<_> = vars.iter().collect();
+
+        shell.formatter().format(&refs).unwrap()
+    }
+
+    #[test]
+    fn test_env_format() {
+        assert_eq!(
+            format(Shell::Env, &[("FOO", "foo"), ("BAR", "it's")]),
+            vec!["FOO=foo", "BAR='it'\\''s'"]
+        );
+    }
+
+    #[test]
+    fn test_bash_format() {
+        assert_eq!(
+            format(Shell::Bash, &[("FOO", "foo bar")]),
+            vec!["export FOO='foo bar'"]
+        );
+    }
+
+    #[test]
+    fn test_fish_format() {
+        assert_eq!(
+            format(Shell::Fish, &[("FOO", r"it's C:\temp")]),
+            vec![r"set -gx FOO 'it\'s C:\\temp'"]
+        );
+    }
+
+    #[test]
+    fn test_powershell_format() {
+        assert_eq!(
+            format(
+                Shell::PowerShell,
+                &[("FOO", "it's $HOME"), ("my-var", "\u{2019}")]
+            ),
+            vec![
+                "$env:FOO = 'it''s $HOME'",
+                "${env:my-var} = '\u{2019}\u{2019}'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nushell_format() {
+        assert_eq!(
+            format(Shell::Nushell, &[("FOO", "say \"hi\"\n"), ("BAR", r"C:\")]),
+            vec![r#"load-env {"FOO": "say \"hi\"\n", "BAR": "C:\\"}"#]
+        );
+    }
+
+    #[test]
+    fn test_csh_format() {
+        assert_eq!(
+            format(Shell::Csh, &[("FOO", "it's!\nnext")]),
+            vec!["setenv FOO 'it'\\''s\\!\\\nnext'"]
+        );
+    }
+
+    #[test]
+    fn test_dotenv_format() {
+        let vars = [
+            EnvVariable::new("FOO", "foo/bar:1".to_string()),
+            EnvVariable::new("BAR", "say \"hi\" to $USER\n".to_string()),
+        ];
+        let refs: Vec<_> = vars.iter().collect();
+
+        assert_eq!(
+            DotenvFormatter.format(&refs).unwrap(),
+            vec!["FOO=foo/bar:1", r#"BAR="say \"hi\" to \$USER\n""#]
+        );
+    }
+
+    #[test]
+    fn test_dotenv_format_round_trip() {
+        let vars = [
+            EnvVariable::new("FOO", "a$b ${BAR} $$".to_string()),
+            EnvVariable::new("BAR", "say \"hi\"\n\\".to_string()),
+        ];
+        let refs: Vec<_> = vars.iter().collect();
+        let content = DotenvFormatter.format(&refs).unwrap().join("\n");
+
+        let config = DotenvParser.parse(&content, Path::new(".env.dev")).unwrap();
+        let parsed = interpolate(&config.overrides["dev"]).unwrap();
+
+        for var in vars.iter() {
+            assert_eq!(parsed[&var.key].value, var.value);
+        }
+    }
+
+    #[test]
+    fn test_docker_format() {
+        let vars = [EnvVariable::new("FOO", "it's \"raw\"".to_string())];
+        let refs: Vec<_> = vars.iter().collect();
+        assert_eq!(
+            DockerFormatter.format(&refs).unwrap(),
+            vec![r#"FOO=it's "raw""#]
+        );
+
+        let vars = [EnvVariable::new("FOO", "multi\nline".to_string())];
+        let refs: Vec<_> = vars.iter().collect();
+        assert_eq!(
+            DockerFormatter.format(&refs).unwrap_err().to_string(),
+            "the value of 'FOO' spans multiple lines, which docker env files do not support"
+        );
+    }
+
+    #[test]
+    fn test_shell_from_str() {
+        assert_eq!("pwsh".parse::<Shell>().unwrap(), Shell::PowerShell);
+        assert_eq!("tcsh".parse::<Shell>().unwrap(), Shell::Csh);
+        assert_eq!(
+            "cmd".parse::<Shell>().unwrap_err().to_string(),
+            "unsupported shell: cmd"
+        );
+    }
+}
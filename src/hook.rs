@@ -0,0 +1,313 @@
+//! Loading and unloading environments when changing directories.
+//!
+//! The shell hook runs `envi export-diff` before every prompt and evaluates
+//! its output. What was applied is kept in the `ENVI_STATE` variable of the
+//! shell, together with the values the applied variables had before, so they
+//! can be restored when leaving the project or switching to another key.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::format::Shell;
+use crate::{EnvVariable, EnvVariableMap};
+
+/// Variable of the shell holding what the hook applied.
+pub const STATE_VAR: &str = "ENVI_STATE";
+
+/// Variable selecting the environment key to apply.
+pub const KEY_VAR: &str = "ENVI_KEY";
+
+/// File next to the project level config file holding the environment key
+/// to apply when [`KEY_VAR`] is not set.
+pub const KEY_FILE_NAME: &str = ".envi-key";
+
+/// The environment to apply to the shell.
+pub struct Target {
+    /// The directory of the project level config file
+    pub dir: PathBuf,
+    pub key: String,
+    pub variables: EnvVariableMap,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Set(EnvVariable),
+    Unset(String),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct State {
+    dir: PathBuf,
+    key: String,
+    applied: BTreeMap<String, String>,
+    /// The values before they were applied, `None` if they were not set
+    previous: BTreeMap<String, Option<String>>,
+}
+
+impl State {
+    fn decode(value: &str) -> Result<State> {
+        STANDARD
+            .decode(value)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .with_context(|| format!("invalid {}, unset it to reset the hook", STATE_VAR))
+    }
+
+    fn encode(&self) -> Result<String> {
+        Ok(STANDARD.encode(serde_json::to_vec(self)?))
+    }
+}
+
+/// The environment key to apply in the project in `dir`, from [`KEY_VAR`]
+/// or the [`KEY_FILE_NAME`] file.
+pub fn active_key(dir: &Path) -> Result<Option<String>> {
+    if let Some(key) = std::env::var(KEY_VAR).ok().filter(|k| !k.is_empty()) {
+        return Ok(Some(key));
+    }
+
+    let path = dir.join(KEY_FILE_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("could not read file `{}`", path.display()))?;
+
+    Ok(Some(content.trim().to_owned()).filter(|k| !k.is_empty()))
+}
+
+/// The changes that turn the shell environment `current` into `target`,
+/// first restoring what the hook applied before. Nothing changes while the
+/// target stays the same.
+pub fn export_diff(
+    current: &BTreeMap<String, String>,
+    target: Option<Target>,
+) -> Result<Vec<Change>> {
+    let state = match current.get(STATE_VAR) {
+        Some(value) => Some(State::decode(value)?),
+        None => None,
+    };
+
+    let target = target.map(|t| {
+        let applied: BTreeMap<_, _> = t
+            .variables
+            .into_values()
+            .map(|v| (v.key, v.value))
+            .collect();
+        (t.dir, t.key, applied)
+    });
+
+    match (&state, &target) {
+        (None, None) => return Ok(Vec::new()),
+        (Some(state), Some((dir, key, applied)))
+            if state.dir == *dir && state.key == *key && state.applied == *applied =>
+        {
+            return Ok(Vec::new())
+        }
+        _ => (),
+    }
+
+    let mut restored = current.clone();
+    for (name, value) in state.iter().flat_map(|s| s.previous.iter()) {
+        match value {
+            Some(value) => restored.insert(name.clone(), value.clone()),
+            None => restored.remove(name),
+        };
+    }
+
+    let mut desired = restored.clone();
+    desired.remove(STATE_VAR);
+
+    if let Some((dir, key, applied)) = target {
+        let state = State {
+            previous: applied
+                .keys()
+                .map(|name| (name.clone(), restored.get(name).cloned()))
+                .collect(),
+            dir,
+            key,
+            applied,
+        };
+        desired.extend(state.applied.clone());
+        desired.insert(STATE_VAR.to_owned(), state.encode()?);
+    }
+
+    let names: BTreeSet<_> = current.keys().chain(desired.keys()).collect();
+
+    Ok(names
+        .into_iter()
+        .filter(|name| current.get(*name) != desired.get(*name))
+        .map(|name| match desired.get(name) {
+            Some(value) => Change::Set(EnvVariable::new(name, value.clone())),
+            None => Change::Unset(name.clone()),
+        })
+        .collect())
+}
+
+/// Fails for the shells there is no hook for.
+pub fn check_shell(shell: Shell) -> Result<()> {
+    match shell {
+        Shell::Bash | Shell::Zsh | Shell::Fish => Ok(()),
+        _ => bail!("the hook is only available for bash, zsh and fish"),
+    }
+}
+
+/// The commands applying `changes` in `shell`.
+pub fn format_changes(shell: Shell, changes: &[Change]) -> Result<Vec<String>> {
+    check_shell(shell)?;
+
+    let formatter = shell.formatter();
+    let mut lines = Vec::new();
+
+    for change in changes.iter() {
+        match change {
+            Change::Set(var) => lines.extend(formatter.format(&[var])?),
+            Change::Unset(name) if shell == Shell::Fish => lines.push(format!("set -e {}", name)),
+            Change::Unset(name) => lines.push(format!("unset {}", name)),
+        }
+    }
+
+    Ok(lines)
+}
+
+/// The script installing the hook, to be evaluated in the shell's startup
+/// file.
+pub fn hook_script(shell: Shell) -> Result<&'static str> {
+    check_shell(shell)?;
+
+    Ok(match shell {
+        Shell::Bash => BASH_HOOK,
+        Shell::Zsh => ZSH_HOOK,
+        _ => FISH_HOOK,
+    })
+}
+
+const BASH_HOOK: &str = r#"_envi_hook() {
+  local previous_exit_status=$?
+  eval "$(envi export-diff bash)"
+  return $previous_exit_status
+}
+if [[ ";${PROMPT_COMMAND[*]:-};" != *";_envi_hook;"* ]]; then
+  PROMPT_COMMAND="_envi_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi"#;
+
+const ZSH_HOOK: &str = r#"_envi_hook() {
+  eval "$(envi export-diff zsh)"
+}
+typeset -ag precmd_functions
+if (( ! ${precmd_functions[(I)_envi_hook]} )); then
+  precmd_functions=(_envi_hook $precmd_functions)
+fi"#;
+
+const FISH_HOOK: &str = r#"function __envi_hook --on-event fish_prompt
+    envi export-diff fish | source
+end"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> BTreeMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn target(key: &str, vars: &[(&str, &str)]) -> Option<Target> {
+        Some(Target {
+            dir: PathBuf::from("/project"),
+            key: key.to_owned(),
+            variables: vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), EnvVariable::new(k, v.to_string())))
+                .collect(),
+        })
+    }
+
+    /// Applies the changes like the shell would.
+    fn apply(current: &mut BTreeMap<String, String>, changes: &[Change]) {
+        for change in changes.iter() {
+            match change {
+                Change::Set(var) => current.insert(var.key.clone(), var.value.clone()),
+                Change::Unset(name) => current.remove(name),
+            };
+        }
+    }
+
+    #[test]
+    fn test_export_diff_load_and_unload() {
+        let mut current = env(&[("HOME", "/root"), ("FOO", "shell_foo")]);
+
+        let changes =
+            export_diff(&current, target("dev", &[("FOO", "foo"), ("BAR", "bar")])).unwrap();
+        apply(&mut current, &changes);
+
+        assert_eq!(current["FOO"], "foo");
+        assert_eq!(current["BAR"], "bar");
+        assert!(current.contains_key(STATE_VAR));
+
+        let changes =
+            export_diff(&current, target("dev", &[("FOO", "foo"), ("BAR", "bar")])).unwrap();
+        assert!(changes.is_empty());
+
+        let changes = export_diff(&current, None).unwrap();
+        apply(&mut current, &changes);
+
+        assert_eq!(current, env(&[("HOME", "/root"), ("FOO", "shell_foo")]));
+        assert!(export_diff(&current, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_export_diff_switch_key() {
+        let mut current = env(&[("FOO", "shell_foo")]);
+
+        let changes = export_diff(
+            &current,
+            target("dev", &[("FOO", "dev_foo"), ("BAR", "bar")]),
+        )
+        .unwrap();
+        apply(&mut current, &changes);
+
+        let changes = export_diff(&current, target("prod", &[("FOO", "prod_foo")])).unwrap();
+        apply(&mut current, &changes);
+
+        assert_eq!(current["FOO"], "prod_foo");
+        assert!(!current.contains_key("BAR"));
+
+        let changes = export_diff(&current, None).unwrap();
+        apply(&mut current, &changes);
+        assert_eq!(current, env(&[("FOO", "shell_foo")]));
+    }
+
+    #[test]
+    fn test_export_diff_invalid_state() {
+        let current = env(&[(STATE_VAR, "nope")]);
+
+        assert_eq!(
+            export_diff(&current, None).unwrap_err().to_string(),
+            "invalid ENVI_STATE, unset it to reset the hook"
+        );
+    }
+
+    #[test]
+    fn test_format_changes() {
+        let changes = vec![
+            Change::Set(EnvVariable::new("FOO", "it's".to_owned())),
+            Change::Unset("BAR".to_owned()),
+        ];
+
+        assert_eq!(
+            format_changes(Shell::Bash, &changes).unwrap(),
+            vec!["export FOO='it'\\''s'", "unset BAR"]
+        );
+        assert_eq!(
+            format_changes(Shell::Fish, &changes).unwrap(),
+            vec![r"set -gx FOO 'it\'s'", "set -e BAR"]
+        );
+        assert!(format_changes(Shell::PowerShell, &changes).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+use crate::EnvVariableMap;
+
+/// Resolves `${VAR}` references in the values of `variables` against the other
+/// variables of the same map.
+///
+/// Supported forms:
+///  * `${VAR}` - value of `VAR`, error if it is not defined
+///  * `${VAR:-default}` - value of `VAR`, or `default` if it is not defined or empty
+///  * `${VAR:?message}` - value of `VAR`, or an error with `message` if it is not defined or empty
+///  * `$$` - a literal `$`
+///
+/// A `$` that is not followed by `{` or `$` is kept as is.
+pub fn interpolate(variables: &EnvVariableMap) -> Result<EnvVariableMap> {
+    let mut resolved = BTreeMap::new();
+
+    for key in variables.keys() {
+        resolve(key, variables, &mut resolved, &mut Vec::new())?;
+    }
+
+    Ok(variables
+        .iter()
+        .map(|(k, v)| {
+            let mut var = v.clone();
+            var.value = resolved.remove(k).unwrap();
+            (k.clone(), var)
+        })
+        .collect())
+}
+
+fn resolve(
+    key: &str,
+    variables: &EnvVariableMap,
+    resolved: &mut BTreeMap<String, String>,
+    chain: &mut Vec<String>,
+) -> Result<String> {
+    if let Some(value) = resolved.get(key) {
+        return Ok(value.clone());
+    }
+
+    if chain.iter().any(|k| k == key) {
+        chain.push(key.to_owned());
+        bail!("cyclic variable reference: {}", chain.join(" -> "));
+    }
+
+    chain.push(key.to_owned());
+    let value = expand(&variables[key].value, key, variables, resolved, chain)?;
+    chain.pop();
+
+    resolved.insert(key.to_owned(), value.clone());
+
+    Ok(value)
+}
+
+fn expand(
+    value: &str,
+    key: &str,
+    variables: &EnvVariableMap,
+    resolved: &mut BTreeMap<String, String>,
+    chain: &mut Vec<String>,
+) -> Result<String> {
+    let mut res = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(pos) = rest.find('$') {
+        res.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if rest.starts_with("$$") {
+            res.push('$');
+            rest = &rest[2..];
+        } else if rest.starts_with("${") {
+            let end = match closing_brace(rest) {
+                Some(end) => end,
+                None => bail!("unterminated '${{' in the value of '{}'", key),
+            };
+            res.push_str(&expand_reference(
+                &rest[2..end],
+                key,
+                variables,
+                resolved,
+                chain,
+            )?);
+            rest = &rest[end + 1..];
+        } else {
+            res.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    res.push_str(rest);
+
+    Ok(res)
+}
+
+/// Expands the inside of a `${...}` reference.
+fn expand_reference(
+    reference: &str,
+    key: &str,
+    variables: &EnvVariableMap,
+    resolved: &mut BTreeMap<String, String>,
+    chain: &mut Vec<String>,
+) -> Result<String> {
+    let (name, modifier) = match reference.find(':') {
+        Some(pos) => (&reference[..pos], Some(&reference[pos + 1..])),
+        None => (reference, None),
+    };
+
+    if name.is_empty() {
+        bail!("empty variable reference in the value of '{}'", key);
+    }
+
+    let value = match variables.contains_key(name) {
+        true => Some(resolve(name, variables, resolved, chain)?),
+        false => None,
+    };
+
+    match modifier {
+        None => match value {
+            Some(v) => Ok(v),
+            None => bail!(
+                "undefined variable '{}' referenced in the value of '{}'",
+                name,
+                key
+            ),
+        },
+        Some(m) if m.starts_with('-') => match value {
+            Some(v) if !v.is_empty() => Ok(v),
+            _ => expand(&m[1..], key, variables, resolved, chain),
+        },
+        Some(m) if m.starts_with('?') => match value {
+            Some(v) if !v.is_empty() => Ok(v),
+            _ => {
+                let message = expand(&m[1..], key, variables, resolved, chain)?;
+                match message.is_empty() {
+                    true => bail!("{}: variable is not set (required by '{}')", name, key),
+                    false => bail!("{}: {}", name, message),
+                }
+            }
+        },
+        Some(m) => bail!(
+            "unsupported modifier ':{}' in '${{{}}}' in the value of '{}'",
+            m,
+            reference,
+            key
+        ),
+    }
+}
+
+/// Returns the position of the `}` closing the `${` at the start of `s`,
+/// taking nested references into account.
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let bytes = s.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvVariable;
+
+    fn var_map(vars: &[(&str, &str)]) -> EnvVariableMap {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), EnvVariable::new(k, v.to_string())))
+            .collect()
+    }
+
+    fn value_of(vars: &[(&str, &str)], key: &str) -> Result<String> {
+        Ok(interpolate(&var_map(vars))?[key].value.clone())
+    }
+
+    #[test]
+    fn test_interpolate_references() {
+        let vars = [
+            ("DB_USER", "admin"),
+            ("DB_HOST", "localhost"),
+            ("DB_PORT", "5432"),
+            (
+                "DATABASE_URL",
+                "postgres://${DB_USER}@${DB_HOST}:${DB_PORT}/app",
+            ),
+        ];
+
+        assert_eq!(
+            value_of(&vars, "DATABASE_URL").unwrap(),
+            "postgres://admin@localhost:5432/app"
+        );
+    }
+
+    #[test]
+    fn test_interpolate_nested_references() {
+        let vars = [("A", "${B}/a"), ("B", "${C}/b"), ("C", "c")];
+
+        assert_eq!(value_of(&vars, "A").unwrap(), "c/b/a");
+    }
+
+    #[test]
+    fn test_interpolate_escaping() {
+        let vars = [("A", "$${B} costs $5 $"), ("B", "b")];
+
+        assert_eq!(value_of(&vars, "A").unwrap(), "${B} costs $5 $");
+    }
+
+    #[test]
+    fn test_interpolate_default() {
+        let vars = [
+            ("A", "${MISSING:-fallback}"),
+            ("B", "${EMPTY:-${C}}"),
+            ("C", "c"),
+            ("D", "${C:-fallback}"),
+            ("EMPTY", ""),
+        ];
+
+        assert_eq!(value_of(&vars, "A").unwrap(), "fallback");
+        assert_eq!(value_of(&vars, "B").unwrap(), "c");
+        assert_eq!(value_of(&vars, "D").unwrap(), "c");
+    }
+
+    #[test]
+    fn test_interpolate_required() {
+        let vars = [
+            ("A", "${MISSING:?must be set for A}"),
+            ("B", "${C:?}"),
+            ("C", "c"),
+        ];
+
+        assert_eq!(value_of(&vars[1..], "B").unwrap(), "c");
+        assert_eq!(
+            value_of(&vars, "A").unwrap_err().to_string(),
+            "MISSING: must be set for A"
+        );
+        assert_eq!(
+            value_of(&[("A", "${MISSING:?}")], "A")
+                .unwrap_err()
+                .to_string(),
+            "MISSING: variable is not set (required by 'A')"
+        );
+    }
+
+    #[test]
+    fn test_interpolate_undefined() {
+        let vars = [("A", "${MISSING}")];
+
+        assert_eq!(
+            value_of(&vars, "A").unwrap_err().to_string(),
+            "undefined variable 'MISSING' referenced in the value of 'A'"
+        );
+    }
+
+    #[test]
+    fn test_interpolate_cycle() {
+        let vars = [("A", "${B}"), ("B", "${C}"), ("C", "${A}")];
+
+        assert_eq!(
+            value_of(&vars, "A").unwrap_err().to_string(),
+            "cyclic variable reference: A -> B -> C -> A"
+        );
+    }
+
+    #[test]
+    fn test_interpolate_unterminated() {
+        let vars = [("A", "${B")];
+
+        assert_eq!(
+            value_of(&vars, "A").unwrap_err().to_string(),
+            "unterminated '${' in the value of 'A'"
+        );
+    }
+}
@@ -1,15 +1,40 @@
 use anyhow::{bail, Context, Result};
+use serde::Serialize;
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{env, fmt};
 
+pub mod completions;
+pub mod edit;
+pub mod environ;
 mod file_parser;
+pub mod format;
+pub mod hook;
+mod interpolate;
+pub mod matrix;
+pub mod parser;
+pub mod schema;
+pub mod secret;
+pub mod trust;
+pub mod validate;
 
-#[derive(Debug, Clone, PartialEq)]
+use file_parser::{ParsedConfig, SkippedValue};
+use parser::ConfigParser;
+use schema::Schema;
+use secret::SecretKey;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct EnvVariable {
     pub key: String,
     pub value: String,
+    /// Config file the value was read from
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    /// Whether the value must be masked in human readable output
+    #[serde(skip)]
+    pub sensitive: bool,
 }
 
 impl fmt::Display for EnvVariable {
@@ -28,32 +53,108 @@ impl EnvVariable {
         EnvVariable {
             key: key.to_owned(),
             value,
+            source: None,
+            sensitive: false,
+        }
+    }
+
+    /// A copy of the variable with its value replaced by [`REDACTED`] if it
+    /// is sensitive.
+    pub fn redacted(&self) -> EnvVariable {
+        let mut var = self.clone();
+        if var.sensitive {
+            var.value = REDACTED.to_owned();
         }
+        var
     }
 }
 
+/// Shown instead of the value of sensitive variables.
+pub const REDACTED: &str = "****";
+
+/// Variables named like `*_PASSWORD` are sensitive even when the schema
+/// doesn't say so.
+const SENSITIVE_NAME_SUFFIXES: [&str; 3] = ["PASSWORD", "TOKEN", "SECRET"];
+
+fn is_sensitive_name(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+
+    SENSITIVE_NAME_SUFFIXES
+        .iter()
+        .any(|suffix| name == *suffix || name.ends_with(&format!("_{}", suffix)))
+}
+
 pub type EnvVariableMap = BTreeMap<String, EnvVariable>;
 
 #[derive(Debug)]
 pub struct EnvironmentVariablesConfig {
+    /// The config file with the highest precedence
     pub source_file: PathBuf,
+    /// All config files in order of increasing precedence
+    pub source_files: Vec<PathBuf>,
     defaults: EnvVariableMap,
     overrides: BTreeMap<String, EnvVariableMap>,
+    extends: BTreeMap<String, Vec<String>>,
+    skipped: Vec<SkippedValue>,
+    schema: Schema,
+    /// Whether encrypted values are left as they are, see
+    /// [`Self::keep_encrypted`]
+    keep_encrypted: bool,
 }
 
 impl EnvironmentVariablesConfig {
-    fn new(
-        defaults: EnvVariableMap,
-        overrides: BTreeMap<String, EnvVariableMap>,
-        source_path: PathBuf,
-    ) -> Self {
+    fn new(config: ParsedConfig, source_path: PathBuf) -> Self {
+        EnvironmentVariablesConfig {
+            source_file: source_path.clone(),
+            source_files: vec![source_path],
+            defaults: config.defaults,
+            overrides: config.overrides,
+            extends: config.extends,
+            skipped: config.skipped,
+            schema: config.schema,
+            keep_encrypted: false,
+        }
+    }
+
+    /// Leaves the encrypted values as they are written instead of decrypting
+    /// them when `keep` is set, so the key file isn't needed. They are only
+    /// checked to be well-formed, and they and the values built from them
+    /// are not checked against the schema.
+    pub fn keep_encrypted(self, keep: bool) -> Self {
         EnvironmentVariablesConfig {
-            source_file: source_path,
-            defaults,
-            overrides,
+            keep_encrypted: keep,
+            ..self
         }
     }
 
+    /// Layers `config` on top of the current one: its variables and schema
+    /// entries replace the ones with the same name, and its `extends` replace
+    /// the ones of the same environment.
+    fn merge(&mut self, config: ParsedConfig, source_path: PathBuf) {
+        self.defaults.extend(config.defaults);
+
+        for (key, overrides) in config.overrides.into_iter() {
+            self.overrides.entry(key).or_default().extend(overrides);
+        }
+
+        self.extends.extend(config.extends);
+        self.skipped.extend(config.skipped);
+        self.schema.extend(config.schema);
+
+        self.source_file = source_path.clone();
+        self.source_files.push(source_path);
+    }
+
+    fn sources(&self) -> String {
+        let files: Vec<_> = self
+            .source_files
+            .iter()
+            .map(|f| f.display().to_string())
+            .collect();
+
+        format!("'{}'", files.join("', '"))
+    }
+
     pub fn keys_diff(&self, key_1: &str, key_2: &str) -> Result<Vec<DiffResult>> {
         let from_vars = self.variables(key_1)?;
         let to_vars = self.variables(key_2)?;
@@ -62,41 +163,145 @@ impl EnvironmentVariablesConfig {
         Ok(diff(&unique_keys, &from_vars, &to_vars))
     }
 
-    pub fn env_diff(&self, key: &str) -> Result<Vec<DiffResult>> {
+    /// Compares the variables of the environment `key` with `env_vars`, the
+    /// environment of a process. Variables that are only set in the process
+    /// are reported if their name starts with one of `prefixes`.
+    pub fn env_diff(
+        &self,
+        key: &str,
+        env_vars: impl IntoIterator<Item = (String, String)>,
+        prefixes: &[String],
+    ) -> Result<Vec<EnvDiffResult>> {
         let config_vars = self.variables(key)?;
 
-        let env_vars: EnvVariableMap = env::vars()
-            .filter(|(key, _)| config_vars.contains_key(key))
-            .map(|(key, value)| (key.clone(), EnvVariable::new(key.as_ref(), value)))
-            .collect();
+        Ok(env_diff(&config_vars, env_vars, prefixes))
+    }
 
-        let unique_keys = unique_keys(&config_vars, &BTreeMap::new());
+    pub fn variables(&self, key: &str) -> Result<EnvVariableMap> {
+        self.resolve_variables(key, !self.keep_encrypted)
+    }
+
+    /// The variables of the environment `key`, with the encrypted values
+    /// decrypted if `decrypt` is set.
+    pub(crate) fn resolve_variables(&self, key: &str, decrypt: bool) -> Result<EnvVariableMap> {
+        self.check_key(key)?;
 
-        Ok(diff(&unique_keys, &env_vars, &config_vars))
+        let mut variables = self.defaults.clone();
+        variables.extend(self.resolve_overrides(key, &mut Vec::new())?);
+        let variables = self.resolve(variables, decrypt)?;
+
+        let errors = schema::check(&self.schema, &variables);
+        if !errors.is_empty() {
+            bail!(
+                "environment key '{}' does not match the schema in {}:\n  {}",
+                key,
+                self.sources(),
+                errors.join("\n  ")
+            );
+        }
+
+        Ok(variables)
     }
 
-    pub fn variables(&self, key: &str) -> Result<EnvVariableMap> {
-        match self.overrides.get(key) {
-            Some(o) => {
-                let mut variables: EnvVariableMap = BTreeMap::new();
+    /// The variables of the environment `key` as they would be without its
+    /// overrides of the defaults, not checked against the schema.
+    pub(crate) fn default_variables(&self, key: &str) -> Result<EnvVariableMap> {
+        self.check_key(key)?;
 
-                for k in unique_keys(&self.defaults, o).iter() {
-                    if let Some(val) = self.defaults.get(k) {
-                        variables.insert(k.to_string(), val.clone());
-                    }
-                    if let Some(val) = o.get(k) {
-                        variables.insert(k.to_string(), val.clone());
-                    }
-                }
+        let mut variables = self.resolve_overrides(key, &mut Vec::new())?;
+        variables.retain(|name, _| {
+            self.schema
+                .get(name)
+                .and_then(|s| s.default.as_ref())
+                .is_none()
+        });
+        variables.extend(self.defaults.clone());
 
-                Ok(variables)
-            }
+        self.resolve(variables, !self.keep_encrypted)
+    }
+
+    /// Adds the schema defaults to `variables` and resolves their values.
+    fn resolve(&self, mut variables: EnvVariableMap, decrypt: bool) -> Result<EnvVariableMap> {
+        schema::apply_defaults(&self.schema, &mut variables);
+        mark_sensitive(&self.schema, &mut variables);
+        match decrypt {
+            true => decrypt_values(&mut variables)?,
+            false => check_encrypted_values(&variables)?,
+        }
+
+        interpolate::interpolate(&variables)
+    }
+
+    /// The names of the variables of the environment `key`, without
+    /// resolving their values.
+    pub fn variable_names(&self, key: &str) -> Result<Vec<String>> {
+        self.check_key(key)?;
+
+        let mut names: BTreeSet<_> = self.defaults.keys().cloned().collect();
+        names.extend(self.resolve_overrides(key, &mut Vec::new())?.into_keys());
+        names.extend(
+            self.schema
+                .iter()
+                .filter(|(_, s)| s.default.is_some())
+                .map(|(name, _)| name.clone()),
+        );
+
+        Ok(names.into_iter().collect())
+    }
+
+    fn check_key(&self, key: &str) -> Result<()> {
+        if !self.overrides.contains_key(key) {
+            bail!(
+                "environment key '{}' does not exists in {}",
+                key,
+                self.sources()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The description of the variables, from the config files and their
+    /// `envi.schema.toml` files.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Collects the overrides of an environment, applying the ones of the
+    /// environments it extends first, in the order they are listed.
+    fn resolve_overrides(&self, key: &str, chain: &mut Vec<String>) -> Result<EnvVariableMap> {
+        if chain.iter().any(|k| k == key) {
+            chain.push(key.to_owned());
+            bail!(
+                "cyclic 'extends' in {}: {}",
+                self.sources(),
+                chain.join(" -> ")
+            );
+        }
+
+        let overrides = match self.overrides.get(key) {
+            Some(o) => o,
             None => bail!(
-                "environment key '{}' does not exists in '{}'",
+                "environment key '{}' extends unknown environment '{}' in {}",
+                chain.last().unwrap(),
                 key,
-                self.source_file.display()
+                self.sources()
             ),
+        };
+
+        chain.push(key.to_owned());
+
+        let mut variables: EnvVariableMap = BTreeMap::new();
+
+        for parent in self.extends.get(key).into_iter().flatten() {
+            variables.extend(self.resolve_overrides(parent, chain)?);
         }
+
+        variables.extend(overrides.clone());
+
+        chain.pop();
+
+        Ok(variables)
     }
 
     pub fn keys(&self) -> impl Iterator<Item = String> + '_ {
@@ -104,66 +309,262 @@ impl EnvironmentVariablesConfig {
     }
 }
 
-enum FileExtension {
-    Json,
-    Toml,
-    Yaml,
-    Unknown(String),
+/// Marks the variables whose values must not be shown: the ones that are
+/// `secret` in the schema, encrypted or named like `*_PASSWORD`, and the ones
+/// referencing any of those.
+fn mark_sensitive(schema: &Schema, variables: &mut EnvVariableMap) {
+    for var in variables.values_mut() {
+        var.sensitive = schema.get(&var.key).is_some_and(|s| s.secret)
+            || secret::is_encrypted(&var.value)
+            || is_sensitive_name(&var.key);
+    }
+
+    loop {
+        let sensitive: Vec<_> = variables
+            .values()
+            .filter(|v| v.sensitive)
+            .map(|v| v.key.clone())
+            .collect();
+
+        let mut changed = false;
+        for var in variables.values_mut().filter(|v| !v.sensitive) {
+            let references = |name: &String| {
+                var.value.contains(&format!("${{{}}}", name))
+                    || var.value.contains(&format!("${{{}:", name))
+            };
+            if sensitive.iter().any(references) {
+                var.sensitive = true;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// Decrypts the encrypted values in place. Decrypted values are taken
+/// literally, so their `$` are escaped for the interpolation.
+fn decrypt_values(variables: &mut EnvVariableMap) -> Result<()> {
+    let mut key = None;
+
+    for var in variables.values_mut() {
+        if !secret::is_encrypted(&var.value) {
+            continue;
+        }
+
+        let key = match &key {
+            Some(k) => k,
+            None => key.insert(
+                SecretKey::load()
+                    .with_context(|| format!("could not decrypt the value of '{}'", var.key))?,
+            ),
+        };
+
+        var.value = key.decrypt(&var.key, &var.value)?.replace('$', "$$");
+    }
+
+    Ok(())
+}
+
+fn check_encrypted_values(variables: &EnvVariableMap) -> Result<()> {
+    variables
+        .values()
+        .filter(|var| secret::is_encrypted(&var.value))
+        .try_for_each(|var| secret::check_encrypted(&var.key, &var.value))
 }
 
 pub fn parse_input_file(path: &Option<PathBuf>) -> Result<EnvironmentVariablesConfig> {
-    let (content, file_path, file_extension) = read_input_file(path)?;
+    parse_input_files(path.as_slice())
+}
+
+/// Parses and layers the given config files, later files taking precedence
+/// over earlier ones. Without any paths the config files are discovered, see
+/// [`discover_config_files`].
+pub fn parse_input_files(paths: &[PathBuf]) -> Result<EnvironmentVariablesConfig> {
+    parse_input_files_as(paths, None)
+}
 
-    let (defaults, overrides) = match file_extension {
-        Some(FileExtension::Json) => file_parser::json::parse_config(&content, &file_path)?,
-        Some(FileExtension::Toml) => file_parser::toml::parse_config(&content, &file_path)?,
-        Some(FileExtension::Yaml) => file_parser::yaml::parse_config(&content, &file_path)?,
-        Some(FileExtension::Unknown(extension)) => {
-            bail!("unsupported input file format: {}", extension)
+/// Like [`parse_input_files`], but the given files are read in the given
+/// format, see [`parser::ParserRegistry::find`], instead of detecting it.
+/// The format of discovered files is always detected. A path of `-`
+/// ([`STDIN_PATH`]) reads the config from the standard input.
+pub fn parse_input_files_as(
+    paths: &[PathBuf],
+    format: Option<&str>,
+) -> Result<EnvironmentVariablesConfig> {
+    let (paths, format) = match paths.is_empty() {
+        true => (discover_config_files(true)?, None),
+        false => (paths.to_vec(), format),
+    };
+
+    let mut res: Option<EnvironmentVariablesConfig> = None;
+
+    for path in paths.into_iter() {
+        let config = parse_config_file(&path, format)?;
+
+        match res.as_mut() {
+            Some(c) => c.merge(config, path),
+            None => res = Some(EnvironmentVariablesConfig::new(config, path)),
         }
-        None => bail!("unsupported input file format"),
+    }
+
+    Ok(res.unwrap())
+}
+
+pub(crate) fn parse_config_file(file_path: &Path, format: Option<&str>) -> Result<ParsedConfig> {
+    // A directory holds a `.env` file and a `.env.<key>` file per environment
+    let mut config = if file_path.is_dir() {
+        file_parser::dotenv::parse_dir(file_path)?
+    } else {
+        let (content, parser) = read_input_file(file_path, format)?;
+        parser.parse(&content, file_path)?
     };
 
-    Ok(EnvironmentVariablesConfig::new(
-        defaults, overrides, file_path,
-    ))
+    let variables = config
+        .defaults
+        .values_mut()
+        .chain(config.overrides.values_mut().flat_map(|o| o.values_mut()));
+    for var in variables {
+        var.source = Some(file_path.to_owned());
+    }
+
+    Ok(config)
 }
 
-fn read_input_file(path: &Option<PathBuf>) -> Result<(String, PathBuf, Option<FileExtension>)> {
-    let default_path = get_default_config_path();
+/// The path of the config file read from the standard input.
+pub const STDIN_PATH: &str = "-";
+
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
 
-    let input_path = match path {
-        Some(path) => path,
-        None => &default_path,
+/// Reads a config file, or the standard input for [`STDIN_PATH`], and finds
+/// the parser for `format` or else detects its format, see
+/// [`parser::ParserRegistry::detect`].
+fn read_input_file(
+    input_path: &Path,
+    format: Option<&str>,
+) -> Result<(String, Arc<dyn ConfigParser>)> {
+    let content = match is_stdin(input_path) {
+        true => std::io::read_to_string(std::io::stdin())
+            .context("could not read the config from stdin")?,
+        false => std::fs::read_to_string(input_path)
+            .with_context(|| format!("could not read config file `{}`", input_path.display()))?,
     };
 
-    let file_extension = match input_path.extension() {
-        Some(ext) => match ext.to_str() {
-            Some("json") => Some(FileExtension::Json),
-            Some("toml") => Some(FileExtension::Toml),
-            Some("yml") => Some(FileExtension::Yaml),
-            Some("yaml") => Some(FileExtension::Yaml),
-            Some(s) => Some(FileExtension::Unknown(s.to_owned())),
-            None => None,
+    let registry = parser::registry();
+    let parser = match format {
+        Some(format) => match registry.find(format) {
+            Some(parser) => parser,
+            None => bail!("unsupported input file format: {}", format),
         },
-        None => None,
+        None => registry.detect(input_path, &content)?,
+    };
+
+    Ok((content, parser))
+}
+
+const CONFIG_FILE_EXTENSIONS: [&str; 4] = ["toml", "yaml", "yml", "json"];
+
+/// Finds the config files to use when none are given explicitly, in order of
+/// increasing precedence:
+///  1. the user level `config.{toml,yaml,yml,json}` in `$XDG_CONFIG_HOME/envi/`
+///     (or `~/.config/envi/`)
+///  2. the project level `.envi.{toml,yaml,yml,json}`
+///  3. the git-ignored `.envi.local.{toml,yaml,yml,json}` next to the project
+///     level file
+///
+/// The project level file is looked for in the current directory and, with
+/// `search_parents`, in its parents up to the root of the git repository or
+/// of the filesystem.
+pub fn discover_config_files(search_parents: bool) -> Result<Vec<PathBuf>> {
+    let cwd = env::current_dir().context("could not determine the current directory")?;
+
+    let mut tried = Vec::new();
+
+    let (dir, project_file) = match find_project_file(&cwd, search_parents, &mut tried) {
+        Some((dir, file)) => (dir, Some(file)),
+        None => (cwd.as_path(), None),
     };
 
-    let content = std::fs::read_to_string(input_path)
-        .with_context(|| format!("could not read config file `{}`", input_path.display()))?;
+    let files: Vec<_> = user_config_file(&mut tried)
+        .into_iter()
+        .chain(project_file)
+        .chain(find_config_file(dir, ".envi.local", &mut tried))
+        .collect();
+
+    if files.is_empty() {
+        let tried: Vec<_> = tried.iter().map(|f| format!("  {}", f.display())).collect();
+        bail!("could not find a config file, tried:\n{}", tried.join("\n"));
+    }
 
-    Ok((content, input_path.clone(), file_extension))
+    Ok(files)
 }
 
-fn get_default_config_path() -> PathBuf {
-    let cwd = std::env::current_dir().unwrap();
-    [cwd, PathBuf::from(".envi.toml")].iter().collect()
+/// The directory of the project level config file, see
+/// [`discover_config_files`].
+pub fn project_dir(search_parents: bool) -> Result<Option<PathBuf>> {
+    let cwd = env::current_dir().context("could not determine the current directory")?;
+
+    Ok(find_project_file(&cwd, search_parents, &mut Vec::new()).map(|(dir, _)| dir.to_owned()))
 }
 
-#[derive(Debug)]
+fn find_project_file<'a>(
+    cwd: &'a Path,
+    search_parents: bool,
+    tried: &mut Vec<PathBuf>,
+) -> Option<(&'a Path, PathBuf)> {
+    for dir in cwd.ancestors() {
+        if let Some(file) = find_config_file(dir, ".envi", tried) {
+            return Some((dir, file));
+        }
+
+        if !search_parents || dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+fn user_config_file(tried: &mut Vec<PathBuf>) -> Option<PathBuf> {
+    find_config_file(&user_config_dir()?, "config", tried)
+}
+
+/// `$XDG_CONFIG_HOME/envi`, or `~/.config/envi`
+fn user_config_dir() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("envi"))
+}
+
+/// Returns the first existing `<dir>/<stem>.<ext>` file for the supported
+/// config file extensions, recording the paths checked in `tried`.
+fn find_config_file(dir: &Path, stem: &str, tried: &mut Vec<PathBuf>) -> Option<PathBuf> {
+    CONFIG_FILE_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+        .find(|f| {
+            tried.push(f.clone());
+            f.is_file()
+        })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
 pub enum DiffStatus {
     Added,
     Deleted,
+    /// The variable is set on both sides, with different values
+    Changed {
+        old: String,
+        new: String,
+    },
 }
 
 impl fmt::Display for DiffStatus {
@@ -171,15 +572,42 @@ impl fmt::Display for DiffStatus {
         match *self {
             DiffStatus::Added => write!(f, "+"),
             DiffStatus::Deleted => write!(f, "-"),
+            DiffStatus::Changed { .. } => write!(f, "~"),
         }
     }
 }
 
+/// Serialized as a flat `{ status, key, value }` record, with the `old` and
+/// `new` values of changed variables.
+#[derive(Clone, Serialize)]
 pub struct DiffResult {
+    #[serde(flatten)]
     pub diff_status: DiffStatus,
+    /// The variable as it is on the side it is set on, the new side for
+    /// changed variables
+    #[serde(flatten)]
     pub env_var: EnvVariable,
 }
 
+impl DiffResult {
+    /// A copy of the result with the values replaced by [`REDACTED`] if the
+    /// variable is sensitive.
+    pub fn redacted(&self) -> DiffResult {
+        let diff_status = match &self.diff_status {
+            DiffStatus::Changed { .. } if self.env_var.sensitive => DiffStatus::Changed {
+                old: REDACTED.to_owned(),
+                new: REDACTED.to_owned(),
+            },
+            status => status.clone(),
+        };
+
+        DiffResult {
+            diff_status,
+            env_var: self.env_var.redacted(),
+        }
+    }
+}
+
 fn diff(
     unique_keys: &[String],
     from_vars: &EnvVariableMap,
@@ -189,18 +617,17 @@ fn diff(
 
     for k in unique_keys.iter() {
         match (from_vars.get(k), to_vars.get(k)) {
-            (Some(from), Some(to)) => {
-                if from.value != to.value {
-                    res.push(DiffResult {
-                        diff_status: DiffStatus::Deleted,
-                        env_var: from.clone(),
-                    });
-                    res.push(DiffResult {
-                        diff_status: DiffStatus::Added,
-                        env_var: to.clone(),
-                    });
-                }
-            }
+            (Some(from), Some(to)) if from.value != to.value => res.push(DiffResult {
+                diff_status: DiffStatus::Changed {
+                    old: from.value.clone(),
+                    new: to.value.clone(),
+                },
+                // Both values are masked if either side is sensitive
+                env_var: EnvVariable {
+                    sensitive: from.sensitive || to.sensitive,
+                    ..to.clone()
+                },
+            }),
             (None, Some(to)) => res.push(DiffResult {
                 diff_status: DiffStatus::Added,
                 env_var: to.clone(),
@@ -218,6 +645,123 @@ fn diff(
     res
 }
 
+/// How a configured variable compares to the one of the current process.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvStatus {
+    /// The variable is configured but not set
+    Missing,
+    /// The variable is set to another value than the configured one
+    Different,
+    /// The variable is set to the configured value
+    Matching,
+    /// The variable is set, not configured, and its name has one of the
+    /// checked prefixes
+    Extra,
+}
+
+/// Serialized as a flat `{ status, key, current, expected }` record.
+#[derive(Clone, Serialize)]
+pub struct EnvDiffResult {
+    pub status: EnvStatus,
+    pub key: String,
+    /// The value set in the process
+    pub current: Option<String>,
+    /// The value of the environment
+    pub expected: Option<String>,
+    #[serde(skip)]
+    pub sensitive: bool,
+}
+
+impl EnvDiffResult {
+    /// A copy of the result with the values replaced by [`REDACTED`] if the
+    /// variable is sensitive.
+    pub fn redacted(&self) -> EnvDiffResult {
+        let redact = |v: &Option<String>| match self.sensitive {
+            true => v.as_ref().map(|_| REDACTED.to_owned()),
+            false => v.clone(),
+        };
+
+        EnvDiffResult {
+            current: redact(&self.current),
+            expected: redact(&self.expected),
+            ..self.clone()
+        }
+    }
+
+    /// The result as a difference between the process (old) and the
+    /// environment (new) side, `None` if the values match.
+    pub fn to_diff(&self) -> Option<DiffResult> {
+        let var = |value: &Option<String>| EnvVariable {
+            sensitive: self.sensitive,
+            ..EnvVariable::new(&self.key, value.clone().unwrap_or_default())
+        };
+
+        let diff_status = match self.status {
+            EnvStatus::Missing => DiffStatus::Added,
+            EnvStatus::Extra => DiffStatus::Deleted,
+            EnvStatus::Different => DiffStatus::Changed {
+                old: self.current.clone().unwrap_or_default(),
+                new: self.expected.clone().unwrap_or_default(),
+            },
+            EnvStatus::Matching => return None,
+        };
+        let env_var = match self.status {
+            EnvStatus::Extra => var(&self.current),
+            _ => var(&self.expected),
+        };
+
+        Some(DiffResult {
+            diff_status,
+            env_var,
+        })
+    }
+}
+
+fn env_diff(
+    config_vars: &EnvVariableMap,
+    env_vars: impl IntoIterator<Item = (String, String)>,
+    prefixes: &[String],
+) -> Vec<EnvDiffResult> {
+    let env_vars: BTreeMap<String, String> = env_vars.into_iter().collect();
+
+    let mut res: Vec<_> = config_vars
+        .values()
+        .map(|var| {
+            let current = env_vars.get(&var.key);
+            let status = match current {
+                None => EnvStatus::Missing,
+                Some(value) if *value == var.value => EnvStatus::Matching,
+                Some(_) => EnvStatus::Different,
+            };
+            EnvDiffResult {
+                status,
+                key: var.key.clone(),
+                current: current.cloned(),
+                expected: Some(var.value.clone()),
+                sensitive: var.sensitive,
+            }
+        })
+        .collect();
+
+    let extra = env_vars.iter().filter(|(key, _)| {
+        !config_vars.contains_key(*key) && prefixes.iter().any(|p| key.starts_with(p.as_str()))
+    });
+    for (key, value) in extra {
+        res.push(EnvDiffResult {
+            status: EnvStatus::Extra,
+            key: key.clone(),
+            current: Some(value.clone()),
+            expected: None,
+            sensitive: is_sensitive_name(key),
+        });
+    }
+
+    res.sort_by(|a, b| a.key.cmp(&b.key));
+
+    res
+}
+
 fn unique_keys(defaults: &EnvVariableMap, overrides: &EnvVariableMap) -> Vec<String> {
     let a: HashSet<_> = defaults.keys().collect();
     let b: HashSet<_> = overrides.keys().collect();
@@ -245,9 +789,246 @@ mod tests {
 
         let res = unique_keys(&a, &b);
         assert_eq!(res.len(), 4);
-        assert_eq!(res.contains(&"a".to_string()), true);
-        assert_eq!(res.contains(&"b".to_string()), true);
-        assert_eq!(res.contains(&"c".to_string()), true);
-        assert_eq!(res.contains(&"d".to_string()), true);
+        assert!(res.contains(&"a".to_string()));
+        assert!(res.contains(&"b".to_string()));
+        assert!(res.contains(&"c".to_string()));
+        assert!(res.contains(&"d".to_string()));
+    }
+
+    fn var_map(vars: &[(&str, &str)]) -> EnvVariableMap {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), EnvVariable::new(k, v.to_string())))
+            .collect()
+    }
+
+    fn config_with_extends(extends: &[(&str, &[&str])]) -> EnvironmentVariablesConfig {
+        let config = ParsedConfig {
+            defaults: var_map(&[("FOO", "foo"), ("BAR", "bar")]),
+            overrides: BTreeMap::from([
+                ("prod".to_string(), var_map(&[("FOO", "prod_foo")])),
+                ("eu".to_string(), var_map(&[("REGION", "eu")])),
+                ("staging".to_string(), var_map(&[("BAR", "staging_bar")])),
+            ]),
+            extends: extends
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.iter().map(|p| p.to_string()).collect()))
+                .collect(),
+            ..Default::default()
+        };
+
+        EnvironmentVariablesConfig::new(config, PathBuf::from("test.toml"))
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut config = config_with_extends(&[("staging", &["prod"])]);
+
+        config.merge(
+            ParsedConfig {
+                defaults: var_map(&[("FOO", "local_foo")]),
+                overrides: BTreeMap::from([
+                    ("staging".to_string(), var_map(&[("BAZ", "local_baz")])),
+                    ("local".to_string(), var_map(&[])),
+                ]),
+                extends: BTreeMap::from([("staging".to_string(), vec![])]),
+                ..Default::default()
+            },
+            PathBuf::from("local.toml"),
+        );
+
+        assert_eq!(
+            config.variables("staging").unwrap(),
+            var_map(&[
+                ("FOO", "local_foo"),
+                ("BAR", "staging_bar"),
+                ("BAZ", "local_baz")
+            ])
+        );
+        assert_eq!(
+            config.keys().collect::<Vec<_>>(),
+            vec!["eu", "local", "prod", "staging"]
+        );
+        assert_eq!(config.source_file, PathBuf::from("local.toml"));
+        assert_eq!(config.sources(), "'test.toml', 'local.toml'");
+    }
+
+    #[test]
+    fn test_variables_extends() {
+        let config = config_with_extends(&[("staging", &["prod", "eu"])]);
+
+        let res = config.variables("staging").unwrap();
+        assert_eq!(
+            res,
+            var_map(&[
+                ("FOO", "prod_foo"),
+                ("BAR", "staging_bar"),
+                ("REGION", "eu")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_variables_extends_chain() {
+        let config = config_with_extends(&[("staging", &["eu"]), ("eu", &["prod"])]);
+
+        let res = config.variables("staging").unwrap();
+        assert_eq!(
+            res,
+            var_map(&[
+                ("FOO", "prod_foo"),
+                ("BAR", "staging_bar"),
+                ("REGION", "eu")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_variables_extends_cycle() {
+        let config = config_with_extends(&[("staging", &["prod"]), ("prod", &["staging"])]);
+
+        let res = config.variables("staging");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "cyclic 'extends' in 'test.toml': staging -> prod -> staging"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let from = var_map(&[("A", "a"), ("B", "b"), ("C", "c")]);
+        let mut to = var_map(&[("B", "b"), ("C", "new_c"), ("D", "d")]);
+        to.get_mut("C").unwrap().sensitive = true;
+
+        let res = diff(&unique_keys(&from, &to), &from, &to);
+        let statuses: Vec<_> = res
+            .iter()
+            .map(|r| (r.env_var.key.as_str(), r.diff_status.clone()))
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ("A", DiffStatus::Deleted),
+                (
+                    "C",
+                    DiffStatus::Changed {
+                        old: "c".to_owned(),
+                        new: "new_c".to_owned()
+                    }
+                ),
+                ("D", DiffStatus::Added),
+            ]
+        );
+        assert_eq!(
+            res[1].redacted().diff_status,
+            DiffStatus::Changed {
+                old: REDACTED.to_owned(),
+                new: REDACTED.to_owned()
+            }
+        );
+        assert_eq!(res[2].redacted().env_var.value, "d");
+    }
+
+    #[test]
+    fn test_env_diff() {
+        let mut config_vars = var_map(&[
+            ("APP_HOST", "localhost"),
+            ("APP_PORT", "8080"),
+            ("APP_TOKEN", "t0ken"),
+            ("DEBUG", "true"),
+        ]);
+        config_vars.get_mut("APP_TOKEN").unwrap().sensitive = true;
+
+        let env_vars = [
+            ("APP_HOST", "localhost"),
+            ("APP_PORT", "9090"),
+            ("APP_MODE", "test"),
+            ("HOME", "/root"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        let res = env_diff(&config_vars, env_vars, &["APP_".to_owned()]);
+        let statuses: Vec<_> = res.iter().map(|r| (r.key.as_str(), r.status)).collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ("APP_HOST", EnvStatus::Matching),
+                ("APP_MODE", EnvStatus::Extra),
+                ("APP_PORT", EnvStatus::Different),
+                ("APP_TOKEN", EnvStatus::Missing),
+                ("DEBUG", EnvStatus::Missing),
+            ]
+        );
+        assert_eq!(res[3].redacted().expected.as_deref(), Some(REDACTED));
+
+        let diffs: Vec<_> = res.iter().filter_map(|r| r.to_diff()).collect();
+        assert_eq!(diffs.len(), 4);
+        assert_eq!(diffs[0].diff_status, DiffStatus::Deleted);
+        assert_eq!(diffs[0].env_var.value, "test");
+        assert_eq!(
+            diffs[1].diff_status,
+            DiffStatus::Changed {
+                old: "9090".to_owned(),
+                new: "8080".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn test_variables_sensitive() {
+        let config = ParsedConfig {
+            defaults: var_map(&[
+                ("DB_PASSWORD", "pw"),
+                ("DB_URL", "db://u:${DB_PASSWORD}@h"),
+                ("API_URL", "https://${API_HOST:-localhost}/${DB_URL}"),
+                ("API_KEY", "key"),
+                ("USER", "u"),
+            ]),
+            overrides: BTreeMap::from([("dev".to_string(), var_map(&[]))]),
+            schema: BTreeMap::from([(
+                "API_KEY".to_string(),
+                schema::VariableSchema {
+                    secret: true,
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let config = EnvironmentVariablesConfig::new(config, PathBuf::from("test.toml"));
+
+        let sensitive: Vec<_> = config
+            .variables("dev")
+            .unwrap()
+            .into_values()
+            .filter(|v| v.sensitive)
+            .map(|v| v.key)
+            .collect();
+
+        assert_eq!(
+            sensitive,
+            vec!["API_KEY", "API_URL", "DB_PASSWORD", "DB_URL"]
+        );
+    }
+
+    #[test]
+    fn test_is_sensitive_name() {
+        assert!(is_sensitive_name("DB_PASSWORD"));
+        assert!(is_sensitive_name("github_token"));
+        assert!(is_sensitive_name("SECRET"));
+        assert!(!is_sensitive_name("TOKENS"));
+        assert!(!is_sensitive_name("PASSWORD_FILE"));
+    }
+
+    #[test]
+    fn test_variables_extends_unknown() {
+        let config = config_with_extends(&[("staging", &["nonsense"])]);
+
+        let res = config.variables("staging");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "environment key 'staging' extends unknown environment 'nonsense' in 'test.toml'"
+        );
     }
 }
@@ -1,6 +1,19 @@
-use anyhow::{Context, Result};
-use std::io::Write;
+use anyhow::{bail, Context, Result};
+use envi::completions;
+use envi::edit::Section;
+use envi::format::{DockerFormatter, DotenvFormatter, Formatter, Shell};
+use envi::hook;
+use envi::matrix::{Cell, Matrix};
+use envi::secret::{self, SecretKey};
+use envi::trust::{self, TrustStore};
+use envi::{DiffResult, DiffStatus, EnvStatus, EnvVariable};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::process::Command;
+use std::str::FromStr;
 use std::{fs::File, path::PathBuf};
+use structopt::clap;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -14,16 +27,180 @@ pub struct Cli {
 
     #[structopt(
         short,
-        long,
+        long = "input-file",
         parse(from_os_str),
-        help = "Input file",
-        env = "ENVI_FILE"
+        number_of_values = 1,
+        help = "Input file, can be given multiple times to layer files on top of each other, `-` reads from stdin. Defaults to the file in ENVI_FILE"
+    )]
+    input_files: Vec<PathBuf>,
+
+    #[structopt(
+        long = "format",
+        help = "Format of the input files (toml, yaml, json, ini, dotenv, properties), detected from their extension or content by default"
+    )]
+    input_format: Option<String>,
+
+    #[structopt(
+        long,
+        global = true,
+        help = "Only look for the config file in the current directory, not in its parents"
     )]
-    input_file: Option<PathBuf>,
+    no_search: bool,
+
+    #[structopt(
+        long,
+        global = true,
+        default_value = "text",
+        possible_values = OutputFormat::NAMES,
+        help = "Output format"
+    )]
+    output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
+    Toml,
+    Dotenv,
+    Docker,
+}
+
+impl OutputFormat {
+    const NAMES: &'static [&'static str] = &["text", "json", "yaml", "toml", "dotenv", "docker"];
+
+    fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Dotenv => "dotenv",
+            OutputFormat::Docker => "docker",
+        }
+    }
+
+    /// Serializes `value` for the structured formats. TOML documents can't
+    /// have a list at the top level, so lists are put under `name` there.
+    fn serialize<T: Serialize + ?Sized>(&self, name: &str, value: &T) -> Result<String> {
+        let res = match self {
+            OutputFormat::Json => serde_json::to_string_pretty(value)?,
+            OutputFormat::Yaml => serde_yaml::to_string(value)?,
+            OutputFormat::Toml => {
+                let value = toml::Value::try_from(value)?;
+                match value {
+                    toml::Value::Table(_) => toml::to_string_pretty(&value)?,
+                    _ => toml::to_string_pretty(&BTreeMap::from([(name, value)]))?,
+                }
+            }
+            _ => bail!(
+                "output format '{}' is not supported for {}",
+                self.name(),
+                name
+            ),
+        };
+
+        Ok(res.trim_end().to_owned())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "toml" => Ok(OutputFormat::Toml),
+            "dotenv" => Ok(OutputFormat::Dotenv),
+            "docker" => Ok(OutputFormat::Docker),
+            _ => bail!("unsupported output format: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    const NAMES: &'static [&'static str] = &["auto", "always", "never"];
+
+    fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => bail!("unsupported color choice: {}", s),
+        }
+    }
+}
+
+/// The formats `matrix` can print the table in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatrixFormat {
+    Table,
+    Csv,
+    Markdown,
+}
+
+impl MatrixFormat {
+    const NAMES: &'static [&'static str] = &["table", "csv", "markdown", "md"];
+}
+
+impl FromStr for MatrixFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(MatrixFormat::Table),
+            "csv" => Ok(MatrixFormat::Csv),
+            "markdown" | "md" => Ok(MatrixFormat::Markdown),
+            _ => bail!("unsupported matrix format: {}", s),
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
 pub enum SubCommand {
+    #[structopt(
+        name = "allow",
+        about = "Allow the config files to be loaded by `exec` and the shell hook"
+    )]
+    Allow(TrustOptions),
+
+    #[structopt(
+        name = "completions",
+        about = "Print the completion script for a shell, completing environment keys and variable names"
+    )]
+    Completions(CompletionsOptions),
+
+    #[structopt(
+        name = "deny",
+        about = "Stop the config files from being loaded by `exec` and the shell hook"
+    )]
+    Deny(TrustOptions),
+
     #[structopt(name = "diff", about = "Show diff between two environments")]
     Diff(DiffOptions),
 
@@ -33,34 +210,288 @@ pub enum SubCommand {
     )]
     EnvDiff(EnvDiffOptions),
 
+    #[structopt(
+        name = "encrypt",
+        about = "Encrypt the value of a variable for a given environment key in the config file"
+    )]
+    Encrypt(EncryptOptions),
+
+    #[structopt(
+        name = "exec",
+        about = "Run a command with the environment variables defined for a given environment key"
+    )]
+    Exec(ExecOptions),
+
+    #[structopt(
+        name = "export-diff",
+        about = "Print the commands that load the environment of the current project into the shell, used by the hook"
+    )]
+    ExportDiff(HookOptions),
+
+    #[structopt(
+        name = "hook",
+        about = "Print the shell hook that loads the environment of a project when entering its directory"
+    )]
+    Hook(HookOptions),
+
     #[structopt(
         name = "keys",
         about = "List all environment keys defined in the config file"
     )]
     Keys(KeysOptions),
 
+    #[structopt(
+        name = "matrix",
+        about = "Show the variables of several environments side by side"
+    )]
+    Matrix(MatrixOptions),
+
+    #[structopt(
+        name = "names",
+        about = "List the names of the variables defined for a given environment key"
+    )]
+    Names(NamesOptions),
+
+    #[structopt(
+        name = "rotate-key",
+        about = "Re-encrypt all encrypted values of the config with a new key"
+    )]
+    RotateKey(RotateKeyOptions),
+
+    #[structopt(
+        name = "set",
+        about = "Set a variable for a given environment key in a TOML or JSON config file, keeping its formatting"
+    )]
+    Set(SetOptions),
+
     #[structopt(
         name = "show",
         about = "Display all defined environment variables for a given environment key"
     )]
     Show(ShowOptions),
+
+    #[structopt(
+        name = "unset",
+        about = "Remove a variable of a given environment key from a TOML or JSON config file, keeping its formatting"
+    )]
+    Unset(UnsetOptions),
+
+    #[structopt(
+        name = "validate",
+        about = "Check the config for invalid names, dropped values and inconsistent environments"
+    )]
+    Validate(ValidateOptions),
 }
 
 #[derive(Debug, StructOpt)]
 pub struct DiffOptions {
     #[structopt(required = true, min_values = 2, max_values = 2)]
     keys: Vec<String>,
+
+    #[structopt(flatten)]
+    view: DiffViewOptions,
 }
 
 #[derive(Debug, StructOpt)]
 pub struct EnvDiffOptions {
     /// Name of the environment key to use
     key: String,
+
+    #[structopt(
+        long = "prefix",
+        number_of_values = 1,
+        help = "Also report variables that are set but not configured when their name starts with the prefix, can be given multiple times"
+    )]
+    prefixes: Vec<String>,
+
+    #[structopt(
+        long,
+        help = "Print nothing and fail if the current environment doesn't match the configured one"
+    )]
+    check: bool,
+
+    #[structopt(
+        long,
+        conflicts_with = "from-file",
+        help = "Compare the environment of the running process with this id instead of the current one (Linux only)"
+    )]
+    pid: Option<u32>,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Compare the environment saved in this file with `env -0` or `env` instead of the current one"
+    )]
+    from_file: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    view: DiffViewOptions,
+}
+
+/// How the subcommands printing values show the sensitive ones.
+#[derive(Debug, StructOpt)]
+pub struct SecretOptions {
+    #[structopt(
+        long,
+        help = "Show the values of sensitive variables instead of masking them"
+    )]
+    reveal: bool,
+}
+
+/// How `diff` and `ediff` show the differences.
+#[derive(Debug, StructOpt)]
+pub struct DiffViewOptions {
+    #[structopt(flatten)]
+    secrets: SecretOptions,
+
+    #[structopt(long, help = "Show the differences as a table with a column per side")]
+    table: bool,
+
+    #[structopt(
+        long,
+        default_value = "auto",
+        possible_values = ColorChoice::NAMES,
+        help = "When to color the output, `auto` colors it on a terminal unless NO_COLOR is set"
+    )]
+    color: ColorChoice,
+
+    #[structopt(long, help = "Exit with 1 if there are differences and 0 otherwise")]
+    exit_code: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct EncryptOptions {
+    /// Name of the environment key to use (omitted with --default) and the variable to encrypt, optionally with a new value as NAME=VALUE
+    #[structopt(
+        required = true,
+        min_values = 1,
+        max_values = 2,
+        value_names = &["KEY", "NAME"]
+    )]
+    args: Vec<String>,
+
+    #[structopt(
+        long,
+        help = "Encrypt the variable in the top level section shared by all environments"
+    )]
+    default: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExecOptions {
+    /// Name of the environment key to use
+    key: String,
+
+    #[structopt(
+        long,
+        help = "Start the command with only the configured variables instead of inheriting the current environment"
+    )]
+    clean: bool,
+
+    /// Command (and its arguments) to run, given after `--`
+    #[structopt(required = true, last = true)]
+    command: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct HookOptions {
+    /// Shell to print the commands for
+    #[structopt(possible_values = &["bash", "zsh", "fish"])]
+    shell: Shell,
 }
 
 #[derive(Debug, StructOpt)]
 pub struct KeysOptions {}
 
+#[derive(Debug, StructOpt)]
+pub struct CompletionsOptions {
+    /// Shell to print the completion script for
+    #[structopt(possible_values = &clap::Shell::variants(), case_insensitive = true)]
+    shell: clap::Shell,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct NamesOptions {
+    /// Name of the environment key to use
+    key: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MatrixOptions {
+    /// Names of the environment keys to compare, all of them if none are given
+    keys: Vec<String>,
+
+    #[structopt(
+        long,
+        help = "Only show the variables whose value differs between the environments"
+    )]
+    varying: bool,
+
+    #[structopt(
+        long,
+        default_value = "table",
+        possible_values = MatrixFormat::NAMES,
+        help = "Format of the table"
+    )]
+    format: MatrixFormat,
+
+    #[structopt(flatten)]
+    secrets: SecretOptions,
+
+    #[structopt(
+        long,
+        default_value = "auto",
+        possible_values = ColorChoice::NAMES,
+        help = "When to color the table, `auto` colors it on a terminal unless NO_COLOR is set"
+    )]
+    color: ColorChoice,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RotateKeyOptions {}
+
+#[derive(Debug, StructOpt)]
+pub struct UnsetOptions {
+    /// Name of the environment key to use (omitted with --default) and the variable to remove
+    #[structopt(
+        required = true,
+        min_values = 1,
+        max_values = 2,
+        value_names = &["KEY", "NAME"]
+    )]
+    args: Vec<String>,
+
+    #[structopt(
+        long,
+        help = "Remove the variable from the top level section shared by all environments"
+    )]
+    default: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SetOptions {
+    /// Name of the environment key to use (omitted with --default) and the variable to set as NAME=value
+    #[structopt(
+        required = true,
+        min_values = 1,
+        max_values = 2,
+        value_names = &["KEY", "NAME=VALUE"]
+    )]
+    args: Vec<String>,
+
+    #[structopt(
+        long,
+        help = "Set the variable in the top level section shared by all environments"
+    )]
+    default: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TrustOptions {}
+
+#[derive(Debug, StructOpt)]
+pub struct ValidateOptions {}
+
 #[derive(Debug, StructOpt)]
 pub struct ShowOptions {
     /// Name of the environment key to use
@@ -74,58 +505,482 @@ pub struct ShowOptions {
 
     #[structopt(long = "name", help = "Variable name to show value for")]
     variable_names: Option<Vec<String>>,
+
+    #[structopt(
+        long,
+        default_value = "env",
+        possible_values = Shell::NAMES,
+        help = "Shell to format the output for"
+    )]
+    format: Shell,
+
+    #[structopt(long, help = "Show which config file each value comes from")]
+    explain: bool,
+
+    #[structopt(flatten)]
+    secrets: SecretOptions,
 }
 
 fn main() -> Result<()> {
     let args = Cli::from_args();
 
     match args.cmd {
+        SubCommand::Allow(_) => run_allow_cmd(&args),
+        SubCommand::Completions(ref opts) => run_completions_cmd(opts),
+        SubCommand::Deny(_) => run_deny_cmd(&args),
         SubCommand::Diff(ref opts) => run_diff_cmd(&args, opts),
         SubCommand::EnvDiff(ref opts) => run_ediff_cmd(&args, opts),
+        SubCommand::Encrypt(ref opts) => run_encrypt_cmd(&args, opts),
+        SubCommand::Exec(ref opts) => run_exec_cmd(&args, opts),
+        SubCommand::ExportDiff(ref opts) => run_export_diff_cmd(&args, opts),
+        SubCommand::Hook(ref opts) => run_hook_cmd(opts),
         SubCommand::Keys(_) => run_keys_cmd(&args),
+        SubCommand::Matrix(ref opts) => run_matrix_cmd(&args, opts),
+        SubCommand::Names(ref opts) => run_names_cmd(&args, opts),
+        SubCommand::RotateKey(_) => run_rotate_key_cmd(&args),
+        SubCommand::Set(ref opts) => run_set_cmd(&args, opts),
         SubCommand::Show(ref opts) => run_show_cmd(&args, opts),
+        SubCommand::Unset(ref opts) => run_unset_cmd(&args, opts),
+        SubCommand::Validate(_) => run_validate_cmd(&args),
+    }
+}
+
+fn parse_input_files(args: &Cli) -> Result<envi::EnvironmentVariablesConfig> {
+    let files = config_files(args)?;
+
+    envi::parse_input_files_as(&files, input_format(args)?)
+}
+
+/// Like [`parse_input_files`], but config files not given with `-i` have to
+/// be allowed with `envi allow` first.
+fn parse_trusted_input_files(args: &Cli) -> Result<envi::EnvironmentVariablesConfig> {
+    let files = config_files(args)?;
+
+    if args.input_files.is_empty() {
+        trust::check(&files)?;
+    }
+
+    envi::parse_input_files_as(&files, input_format(args)?)
+}
+
+/// The format given with `--format`, which only applies to the config files
+/// given with `-i` or `ENVI_FILE`.
+fn input_format(args: &Cli) -> Result<Option<&str>> {
+    let discovered = args.input_files.is_empty() && env_file().is_none();
+
+    match (&args.input_format, discovered) {
+        (Some(_), true) => {
+            bail!("--format only applies to the config files given with -i or ENVI_FILE")
+        }
+        (format, _) => Ok(format.as_deref()),
+    }
+}
+
+/// The config file set in `ENVI_FILE`, if any.
+fn env_file() -> Option<PathBuf> {
+    std::env::var_os("ENVI_FILE")
+        .filter(|file| !file.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The config files given with `-i`, the one in `ENVI_FILE` or the
+/// discovered ones.
+fn config_files(args: &Cli) -> Result<Vec<PathBuf>> {
+    if !args.input_files.is_empty() {
+        return Ok(args.input_files.clone());
+    }
+
+    // Not a default of `-i`, as a variable set in the environment doesn't
+    // make the file trusted like passing it explicitly does
+    match env_file() {
+        Some(file) => Ok(vec![file]),
+        None => envi::discover_config_files(!args.no_search),
     }
 }
 
+/// The single config file `set`, `unset` and `encrypt` can write to.
+fn config_file_to_edit(args: &Cli) -> Result<PathBuf> {
+    let files = config_files(args)?;
+
+    match files.as_slice() {
+        [file] => Ok(file.clone()),
+        _ => {
+            let files: Vec<_> = files.iter().map(|f| f.display().to_string()).collect();
+            bail!(
+                "the config is layered from multiple files ('{}'), use -i to choose the one to edit",
+                files.join("', '")
+            )
+        }
+    }
+}
+
+/// Splits the positional arguments of `set`, `unset` and `encrypt` into the section to
+/// edit and the remaining argument.
+fn edit_section(default: bool, args: &[String]) -> Result<(Section<'_>, &str)> {
+    match (default, args) {
+        (true, [arg]) => Ok((Section::Defaults, arg)),
+        (false, [key, arg]) => Ok((Section::Environment(key), arg)),
+        (true, _) => bail!("no environment key can be given with --default"),
+        (false, _) => bail!("an environment key is required unless --default is given"),
+    }
+}
+
+fn run_allow_cmd(args: &Cli) -> Result<()> {
+    let files = trust::files_to_trust(&config_files(args)?);
+
+    let mut store = TrustStore::load()?;
+    for file in files.iter() {
+        store.allow(file)?;
+    }
+    store.save()?;
+
+    for file in files.iter() {
+        println!("Allowed '{}'", file.display());
+    }
+
+    Ok(())
+}
+
+fn run_deny_cmd(args: &Cli) -> Result<()> {
+    let files = trust::files_to_trust(&config_files(args)?);
+
+    let mut store = TrustStore::load()?;
+    let denied: Vec<_> = files
+        .iter()
+        .map(|file| store.deny(file))
+        .collect::<Result<_>>()?;
+    store.save()?;
+
+    for (file, denied) in files.iter().zip(denied) {
+        match denied {
+            true => println!("Denied '{}'", file.display()),
+            false => println!("'{}' was not allowed", file.display()),
+        }
+    }
+
+    Ok(())
+}
+
 fn run_diff_cmd(args: &Cli, opts: &DiffOptions) -> Result<()> {
-    let config = envi::parse_input_file(&args.input_file)?;
+    // Masked values don't need to be decrypted
+    let config = parse_input_files(args)?.keep_encrypted(!opts.view.secrets.reveal);
 
     let env_1 = opts.keys.first().unwrap();
     let env_2 = opts.keys.last().unwrap();
 
     let diffs = config.keys_diff(env_1, env_2)?;
+    let diffs: Vec<_> = match opts.view.secrets.reveal {
+        true => diffs,
+        false => diffs.iter().map(|d| d.redacted()).collect(),
+    };
+
+    if args.output != OutputFormat::Text {
+        println!("{}", args.output.serialize("diff", &diffs)?);
+    } else if !diffs.is_empty() {
+        print_diff(&opts.view, (env_1, env_2), &diffs);
 
-    if !diffs.is_empty() {
-        println!("--- {}", env_1);
-        println!("+++ {}", env_2);
+        let (mut changed, mut added, mut removed) = (0, 0, 0);
+        for diff in diffs.iter() {
+            match diff.diff_status {
+                DiffStatus::Changed { .. } => changed += 1,
+                DiffStatus::Added => added += 1,
+                DiffStatus::Deleted => removed += 1,
+            }
+        }
+        println!("{} changed, {} added, {} removed", changed, added, removed);
     }
 
-    for x in diffs.into_iter() {
-        println!("{} {}", x.diff_status, x.env_var);
+    if opts.view.exit_code && !diffs.is_empty() {
+        std::process::exit(1);
     }
 
     Ok(())
 }
 
 fn run_ediff_cmd(args: &Cli, opts: &EnvDiffOptions) -> Result<()> {
-    let config = envi::parse_input_file(&args.input_file)?;
+    let config = parse_input_files(args)?;
+
+    let (label, env_vars) = match (opts.pid, &opts.from_file) {
+        (Some(pid), _) => (format!("pid {}", pid), envi::environ::read_process(pid)?),
+        (None, Some(file)) => (file.display().to_string(), envi::environ::read_dump(file)?),
+        (None, None) => ("env".to_owned(), std::env::vars().collect()),
+    };
+
+    let results = config.env_diff(&opts.key, env_vars, &opts.prefixes)?;
+    let results: Vec<_> = match opts.view.secrets.reveal {
+        true => results,
+        false => results.iter().map(|r| r.redacted()).collect(),
+    };
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    let mismatches = results.len() - count(EnvStatus::Matching);
+    let summary = format!(
+        "{} different, {} missing, {} extra",
+        count(EnvStatus::Different),
+        count(EnvStatus::Missing),
+        count(EnvStatus::Extra)
+    );
+
+    if opts.check {
+        if mismatches > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    let diffs = config.env_diff(&opts.key)?;
+    if args.output != OutputFormat::Text {
+        println!("{}", args.output.serialize("variables", &results)?);
+    } else if mismatches > 0 {
+        let diffs: Vec<_> = results.iter().filter_map(|r| r.to_diff()).collect();
+        print_diff(&opts.view, (&label, &opts.key), &diffs);
 
-    if !diffs.is_empty() {
-        println!("--- env");
-        println!("+++ {}", opts.key);
+        println!("{}, {} matching", summary, count(EnvStatus::Matching));
     }
 
-    for x in diffs.into_iter() {
-        println!("{} {}", x.diff_status, x.env_var);
+    if opts.view.exit_code && mismatches > 0 {
+        std::process::exit(1);
     }
 
     Ok(())
 }
 
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BOLD: &str = "\x1b[1m";
+
+fn paint(s: &str, color: &str, enabled: bool) -> String {
+    match enabled {
+        true => format!("{}{}\x1b[0m", color, s),
+        false => s.to_owned(),
+    }
+}
+
+fn status_color(status: &DiffStatus) -> &'static str {
+    match status {
+        DiffStatus::Added => GREEN,
+        DiffStatus::Deleted => RED,
+        DiffStatus::Changed { .. } => YELLOW,
+    }
+}
+
+fn print_diff(opts: &DiffViewOptions, labels: (&str, &str), diffs: &[DiffResult]) {
+    let color = opts.color.enabled();
+
+    let lines = match opts.table {
+        true => diff_table(labels, diffs, color),
+        false => diff_unified(labels, diffs, color),
+    };
+    for line in lines.iter() {
+        println!("{}", line);
+    }
+}
+
+/// `-`/`+` lines like `diff -u`, a changed variable is shown as its old line
+/// followed by its new one.
+fn diff_unified(labels: (&str, &str), diffs: &[DiffResult], color: bool) -> Vec<String> {
+    let mut lines = vec![
+        paint(&format!("--- {}", labels.0), BOLD, color),
+        paint(&format!("+++ {}", labels.1), BOLD, color),
+    ];
+
+    for x in diffs.iter() {
+        match &x.diff_status {
+            DiffStatus::Changed { old, .. } => {
+                let old = EnvVariable::new(&x.env_var.key, old.clone());
+                lines.push(paint(&format!("- {}", old), RED, color));
+                lines.push(paint(&format!("+ {}", x.env_var), GREEN, color));
+            }
+            status => lines.push(paint(
+                &format!("{} {}", status, x.env_var),
+                status_color(status),
+                color,
+            )),
+        }
+    }
+
+    lines
+}
+
+/// A row per variable with its value on both sides.
+fn diff_table(labels: (&str, &str), diffs: &[DiffResult], color: bool) -> Vec<String> {
+    let escape = |s: &str| s.replace('\n', "\\n");
+
+    let mut rows = vec![(
+        None,
+        ["KEY".to_owned(), labels.0.to_owned(), labels.1.to_owned()],
+    )];
+    for x in diffs.iter() {
+        let (old, new) = match &x.diff_status {
+            DiffStatus::Added => (String::new(), escape(&x.env_var.value)),
+            DiffStatus::Deleted => (escape(&x.env_var.value), String::new()),
+            DiffStatus::Changed { old, new } => (escape(old), escape(new)),
+        };
+        rows.push((Some(&x.diff_status), [x.env_var.key.clone(), old, new]));
+    }
+
+    let width = |i: usize| {
+        rows.iter()
+            .map(|(_, cells)| cells[i].chars().count())
+            .max()
+            .unwrap()
+    };
+    let (key_width, old_width) = (width(0), width(1));
+
+    rows.iter()
+        .map(|(status, [key, old, new])| {
+            let marker = status.map_or(" ".to_owned(), |s| s.to_string());
+            let line = format!("{} {:key_width$}  {:old_width$}  {}", marker, key, old, new);
+            let line = line.trim_end();
+            match status {
+                Some(status) => paint(line, status_color(status), color),
+                None => paint(line, BOLD, color),
+            }
+        })
+        .collect()
+}
+
+fn run_encrypt_cmd(args: &Cli, opts: &EncryptOptions) -> Result<()> {
+    let (section, arg) = edit_section(opts.default, &opts.args)?;
+
+    let file = config_file_to_edit(args)?;
+
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, Some(value.to_owned())),
+        None => (arg, None),
+    };
+    // Variables of nested tables would be written as new top level keys
+    envi::edit::check_not_flattened(&file, input_format(args)?, section, name)?;
+
+    let value = match value {
+        Some(value) => value,
+        None => envi::edit::get_variable(&file, input_format(args)?, section, name)?,
+    };
+
+    if secret::is_encrypted(&value) {
+        bail!("'{}' in {} is already encrypted", name, section);
+    }
+
+    let key_file = secret::key_file_path()?;
+    if !key_file.exists() {
+        eprintln!("Created a new key file at '{}'", key_file.display());
+    }
+    let key = SecretKey::load_or_create()?;
+
+    let value = key.encrypt(name, &value)?;
+    envi::edit::set_variable(&file, input_format(args)?, section, name, &value)
+}
+
+fn run_exec_cmd(args: &Cli, opts: &ExecOptions) -> Result<()> {
+    let config = parse_trusted_input_files(args)?;
+
+    let variables = config.variables(&opts.key)?;
+
+    let (program, program_args) = opts.command.split_first().unwrap();
+
+    let mut cmd = Command::new(program);
+    cmd.args(program_args);
+
+    if opts.clean {
+        cmd.env_clear();
+    }
+
+    cmd.envs(variables.values().map(|v| (&v.key, &v.value)));
+
+    exec(cmd, program)
+}
+
+// On Unix the current process is replaced by the command, so signals are
+// delivered straight to it and its exit status becomes ours.
+#[cfg(unix)]
+fn exec(mut cmd: Command, program: &str) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let err = cmd.exec();
+
+    Err(err).with_context(|| format!("could not run command `{}`", program))
+}
+
+#[cfg(not(unix))]
+fn exec(mut cmd: Command, program: &str) -> Result<()> {
+    let status = cmd
+        .status()
+        .with_context(|| format!("could not run command `{}`", program))?;
+
+    std::process::exit(status.code().unwrap_or(1))
+}
+
+fn run_export_diff_cmd(args: &Cli, opts: &HookOptions) -> Result<()> {
+    // The hook runs before every prompt, a broken config unloads the
+    // environment instead of keeping a stale one
+    let target = match hook_target(args) {
+        Ok(target) => target,
+        Err(e) => {
+            eprintln!("envi: {:#}", e);
+            None
+        }
+    };
+
+    let current: BTreeMap<_, _> = std::env::vars().collect();
+    let changes = hook::export_diff(&current, target)?;
+
+    for line in hook::format_changes(opts.shell, &changes)? {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// The environment of the project the current directory is in, if a key is
+/// selected for it.
+fn hook_target(args: &Cli) -> Result<Option<hook::Target>> {
+    let dir = match envi::project_dir(!args.no_search)? {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    let key = match hook::active_key(&dir)? {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+
+    let config = parse_trusted_input_files(args)?;
+    let variables = config.variables(&key)?;
+
+    Ok(Some(hook::Target {
+        dir,
+        key,
+        variables,
+    }))
+}
+
+fn run_hook_cmd(opts: &HookOptions) -> Result<()> {
+    println!("{}", hook::hook_script(opts.shell)?);
+
+    Ok(())
+}
+
+fn run_completions_cmd(opts: &CompletionsOptions) -> Result<()> {
+    let mut script = Vec::new();
+    Cli::clap().gen_completions_to("envi", opts.shell, &mut script);
+
+    let script = String::from_utf8(script)?;
+    print!(
+        "{}",
+        completions::add_dynamic_completions(opts.shell, script)
+    );
+
+    Ok(())
+}
+
 fn run_keys_cmd(args: &Cli) -> Result<()> {
-    let config = envi::parse_input_file(&args.input_file)?;
+    let config = parse_input_files(args)?;
+
+    if args.output != OutputFormat::Text {
+        let keys: Vec<_> = config.keys().collect();
+        println!("{}", args.output.serialize("keys", &keys)?);
+        return Ok(());
+    }
 
     for key in config.keys() {
         println!("{}", key)
@@ -134,8 +989,237 @@ fn run_keys_cmd(args: &Cli) -> Result<()> {
     Ok(())
 }
 
+fn run_names_cmd(args: &Cli, opts: &NamesOptions) -> Result<()> {
+    let config = parse_input_files(args)?;
+    let names = config.variable_names(&opts.key)?;
+
+    if args.output != OutputFormat::Text {
+        println!("{}", args.output.serialize("names", &names)?);
+        return Ok(());
+    }
+
+    for name in names {
+        println!("{}", name)
+    }
+
+    Ok(())
+}
+
+fn run_matrix_cmd(args: &Cli, opts: &MatrixOptions) -> Result<()> {
+    // Masked values don't need to be decrypted
+    let config = parse_input_files(args)?.keep_encrypted(!opts.secrets.reveal);
+
+    let mut matrix = envi::matrix::matrix(&config, &opts.keys)?;
+    if opts.varying {
+        matrix = matrix.varying();
+    }
+    if !opts.secrets.reveal {
+        matrix = matrix.redacted();
+    }
+
+    if args.output != OutputFormat::Text {
+        if opts.format != MatrixFormat::Table {
+            bail!("--format can't be combined with --output");
+        }
+        println!("{}", args.output.serialize("variables", &matrix)?);
+        return Ok(());
+    }
+
+    let lines = match opts.format {
+        MatrixFormat::Table => matrix_table(&matrix, opts.color.enabled()),
+        MatrixFormat::Csv => matrix_csv(&matrix),
+        MatrixFormat::Markdown => matrix_markdown(&matrix),
+    };
+    for line in lines.iter() {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// The header and the rows of a matrix, with the color of each cell. Cells
+/// that differ from the default are yellow, missing ones are red.
+fn matrix_cells(matrix: &Matrix) -> Vec<Vec<(String, Option<&'static str>)>> {
+    let mut header = vec![("NAME".to_owned(), None), ("defaults".to_owned(), None)];
+    header.extend(matrix.keys.iter().map(|k| (k.clone(), None)));
+
+    let mut rows = vec![header];
+    for row in matrix.rows.iter() {
+        let mut cells = vec![
+            (row.name.clone(), None),
+            (row.default.clone().unwrap_or_default(), None),
+        ];
+        cells.extend(row.cells.iter().map(|cell| match cell {
+            Cell::Default(value) => (value.clone(), None),
+            Cell::Overridden(value) => (value.clone(), Some(YELLOW)),
+            Cell::Unknown(value) => (value.clone(), None),
+            Cell::Missing => ("-".to_owned(), Some(RED)),
+        }));
+        rows.push(cells);
+    }
+
+    rows
+}
+
+fn matrix_table(matrix: &Matrix, color: bool) -> Vec<String> {
+    let rows: Vec<Vec<_>> = matrix_cells(matrix)
+        .into_iter()
+        .map(|cells| {
+            cells
+                .into_iter()
+                .map(|(value, color)| (value.replace('\n', "\\n"), color))
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|r| r[i].0.chars().count()).max().unwrap())
+        .collect();
+
+    rows.iter()
+        .enumerate()
+        .map(|(i, cells)| {
+            let line: Vec<_> = cells
+                .iter()
+                .zip(widths.iter())
+                .map(|((value, cell_color), width)| {
+                    let padded = format!("{:width$}", value, width = width);
+                    match (i, cell_color) {
+                        (0, _) => paint(&padded, BOLD, color),
+                        (_, Some(cell_color)) => paint(&padded, cell_color, color),
+                        (_, None) => padded,
+                    }
+                })
+                .collect();
+            line.join("  ").trim_end().to_owned()
+        })
+        .collect()
+}
+
+/// RFC 4180 CSV, missing values are empty.
+fn matrix_csv(matrix: &Matrix) -> Vec<String> {
+    let field = |s: &str| match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_owned(),
+    };
+
+    let mut header = vec!["name", "defaults"];
+    header.extend(matrix.keys.iter().map(|k| k.as_str()));
+
+    let mut lines = vec![header
+        .iter()
+        .map(|h| field(h))
+        .collect::<Vec<_>>()
+        .join(",")];
+    for row in matrix.rows.iter() {
+        let mut fields = vec![
+            field(&row.name),
+            field(row.default.as_deref().unwrap_or("")),
+        ];
+        fields.extend(
+            row.cells
+                .iter()
+                .map(|cell| field(cell.value().unwrap_or(""))),
+        );
+        lines.push(fields.join(","));
+    }
+
+    lines
+}
+
+/// A GitHub flavored Markdown table, values that differ from the default are
+/// bold and missing ones are shown as `-`.
+fn matrix_markdown(matrix: &Matrix) -> Vec<String> {
+    let escape = |s: &str| {
+        let mut escaped = String::new();
+        for c in s.chars() {
+            match c {
+                '\\' | '|' | '*' | '_' | '`' | '<' => escaped.extend(['\\', c]),
+                '\n' => escaped.push_str("<br>"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    };
+
+    let mut lines = Vec::new();
+    for (i, cells) in matrix_cells(matrix).iter().enumerate() {
+        let cells: Vec<_> = cells
+            .iter()
+            .map(|(value, color)| match (*color, value.is_empty()) {
+                (Some(YELLOW), false) => format!("**{}**", escape(value)),
+                _ => escape(value),
+            })
+            .collect();
+        lines.push(format!("| {} |", cells.join(" | ")));
+
+        if i == 0 {
+            lines.push(format!("|{}", "---|".repeat(cells.len())));
+        }
+    }
+
+    lines
+}
+
+fn run_rotate_key_cmd(args: &Cli) -> Result<()> {
+    let files = config_files(args)?;
+
+    let count = secret::rotate_key(&files, input_format(args)?)?;
+
+    let key_file = secret::key_file_path()?;
+    println!(
+        "Re-encrypted {} value(s) with the new key in '{}', the previous key is kept in '{}'",
+        count,
+        key_file.display(),
+        secret::backup_path(&key_file).display()
+    );
+
+    Ok(())
+}
+
+fn run_set_cmd(args: &Cli, opts: &SetOptions) -> Result<()> {
+    let (section, assignment) = edit_section(opts.default, &opts.args)?;
+
+    let (name, value) = match assignment.split_once('=') {
+        Some((name, value)) => (name, value),
+        None => bail!("expected NAME=value, got '{}'", assignment),
+    };
+
+    let file = config_file_to_edit(args)?;
+
+    envi::edit::set_variable(&file, input_format(args)?, section, name, value)
+}
+
+fn run_unset_cmd(args: &Cli, opts: &UnsetOptions) -> Result<()> {
+    let (section, name) = edit_section(opts.default, &opts.args)?;
+
+    let file = config_file_to_edit(args)?;
+
+    envi::edit::unset_variable(&file, input_format(args)?, section, name)
+}
+
+fn run_validate_cmd(args: &Cli) -> Result<()> {
+    let config = parse_input_files(args)?;
+
+    let issues = envi::validate::validate(&config);
+
+    if args.output != OutputFormat::Text {
+        println!("{}", args.output.serialize("issues", &issues)?);
+    } else {
+        for issue in issues.iter() {
+            println!("{}", issue);
+        }
+    }
+
+    match issues.len() {
+        0 => Ok(()),
+        1 => bail!("found 1 problem"),
+        n => bail!("found {} problems", n),
+    }
+}
+
 fn run_show_cmd(args: &Cli, opts: &ShowOptions) -> Result<()> {
-    let config = envi::parse_input_file(&args.input_file)?;
+    let config = parse_input_files(args)?;
 
     let all_vars = config.variables(&opts.key)?.into_iter();
 
@@ -144,21 +1228,43 @@ fn run_show_cmd(args: &Cli, opts: &ShowOptions) -> Result<()> {
         None => all_vars.collect(),
     };
 
-    let mut res = Vec::new();
-
-    for (_, v) in variables.iter() {
-        let s = if opts.value_only {
-            v.value.to_string()
-        } else {
-            format!("{}", v)
-        };
+    if args.output != OutputFormat::Text && (opts.value_only || opts.format != Shell::Env) {
+        bail!("--value-only and --format can't be combined with --output");
+    }
 
-        res.push(s);
+    if opts.explain
+        && (args.output != OutputFormat::Text || opts.value_only || opts.format != Shell::Env)
+    {
+        bail!("--explain can't be combined with --value-only, --format or --output");
     }
 
+    let variables: Vec<_> = match opts.secrets.reveal {
+        true => variables.into_iter().map(|(_, v)| v).collect(),
+        false => variables.iter().map(|(_, v)| v.redacted()).collect(),
+    };
+    let vars: Vec<_> = variables.iter().collect();
+
+    let res: Vec<String> = match args.output {
+        OutputFormat::Text if opts.explain => vars
+            .iter()
+            .map(|v| match &v.source {
+                Some(source) => format!("{}  # {}", v, source.display()),
+                None => v.to_string(),
+            })
+            .collect(),
+        OutputFormat::Text if opts.value_only => vars.iter().map(|v| v.value.to_string()).collect(),
+        OutputFormat::Text => opts.format.formatter().format(&vars)?,
+        OutputFormat::Dotenv => DotenvFormatter.format(&vars)?,
+        OutputFormat::Docker => DockerFormatter.format(&vars)?,
+        _ => {
+            let map: BTreeMap<_, _> = vars.iter().map(|v| (&v.key, &v.value)).collect();
+            vec![args.output.serialize("variables", &map)?]
+        }
+    };
+
     match &opts.output_file {
         Some(f) => {
-            let mut w = File::create(&f)
+            let mut w = File::create(f)
                 .with_context(|| format!("could not write to file `{}`", f.display()))?;
             for s in res.iter() {
                 writeln!(&mut w, "{}", s)?;
@@ -0,0 +1,334 @@
+use anyhow::Result;
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeSet;
+
+use crate::{secret, EnvironmentVariablesConfig, REDACTED};
+
+/// The value of a variable in one environment of a [`MatrixRow`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    /// The value comes from the defaults
+    Default(String),
+    /// The environment sets a value that differs from the default, or the
+    /// variable has no default
+    Overridden(String),
+    /// The environment sets an encrypted value, or one built from it, that
+    /// can't be compared with the default without decrypting it
+    Unknown(String),
+    /// The variable is not set in the environment
+    Missing,
+}
+
+impl Cell {
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Cell::Default(value) | Cell::Overridden(value) | Cell::Unknown(value) => Some(value),
+            Cell::Missing => None,
+        }
+    }
+}
+
+/// A variable and its value in every compared environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixRow {
+    pub name: String,
+    /// The value of the defaults section or the schema default, as written
+    /// in the config file
+    pub default: Option<String>,
+    /// The values in the order of [`Matrix::keys`]
+    pub cells: Vec<Cell>,
+    /// Whether the variable is sensitive in any of the environments
+    pub sensitive: bool,
+}
+
+impl MatrixRow {
+    /// Whether the environments don't all have the same value.
+    pub fn varies(&self) -> bool {
+        let mut values = self.cells.iter().map(|c| c.value());
+        match values.next() {
+            Some(first) => values.any(|v| v != first),
+            None => false,
+        }
+    }
+
+    /// A copy of the row with the values replaced by [`REDACTED`] if the
+    /// variable is sensitive.
+    pub fn redacted(&self) -> MatrixRow {
+        if !self.sensitive {
+            return self.clone();
+        }
+
+        let redact = |cell: &Cell| match cell {
+            Cell::Default(_) => Cell::Default(REDACTED.to_owned()),
+            Cell::Overridden(_) => Cell::Overridden(REDACTED.to_owned()),
+            Cell::Unknown(_) => Cell::Unknown(REDACTED.to_owned()),
+            Cell::Missing => Cell::Missing,
+        };
+
+        MatrixRow {
+            default: self.default.as_ref().map(|_| REDACTED.to_owned()),
+            cells: self.cells.iter().map(redact).collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// The variables of several environments side by side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub keys: Vec<String>,
+    pub rows: Vec<MatrixRow>,
+}
+
+impl Matrix {
+    /// The matrix with only the rows whose value differs between the
+    /// environments.
+    pub fn varying(self) -> Matrix {
+        Matrix {
+            rows: self.rows.into_iter().filter(|r| r.varies()).collect(),
+            ..self
+        }
+    }
+
+    pub fn redacted(&self) -> Matrix {
+        Matrix {
+            keys: self.keys.clone(),
+            rows: self.rows.iter().map(|r| r.redacted()).collect(),
+        }
+    }
+}
+
+/// Serialized as a list of `{ name, default, values }` records, with the
+/// values keyed by environment.
+impl Serialize for Matrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.rows.iter().map(|row| Record {
+            row,
+            keys: &self.keys,
+        }))
+    }
+}
+
+struct Record<'a> {
+    row: &'a MatrixRow,
+    keys: &'a [String],
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("MatrixRow", 3)?;
+        s.serialize_field("name", &self.row.name)?;
+        s.serialize_field("default", &self.row.default)?;
+        s.serialize_field("values", &Values(self))?;
+        s.end()
+    }
+}
+
+struct Values<'a>(&'a Record<'a>);
+
+impl Serialize for Values<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Record { row, keys } = self.0;
+
+        let mut m = serializer.serialize_map(Some(keys.len()))?;
+        for (key, cell) in keys.iter().zip(row.cells.iter()) {
+            m.serialize_entry(key, &cell.value())?;
+        }
+        m.end()
+    }
+}
+
+/// Resolves the variables of the environments `keys`, or of all of them if
+/// `keys` is empty.
+pub fn matrix(config: &EnvironmentVariablesConfig, keys: &[String]) -> Result<Matrix> {
+    let keys: Vec<String> = match keys.is_empty() {
+        true => config.keys().collect(),
+        false => keys.to_vec(),
+    };
+
+    let mut environments = Vec::new();
+    for key in keys.iter() {
+        let variables = config.variables(key)?;
+        let overrides = config.resolve_overrides(key, &mut Vec::new())?;
+        // Without them, every override counts as different from the default
+        let defaults = config.default_variables(key).unwrap_or_default();
+        environments.push((variables, overrides, defaults));
+    }
+
+    let names: BTreeSet<_> = environments
+        .iter()
+        .flat_map(|(variables, ..)| variables.keys())
+        .chain(config.defaults.keys())
+        .collect();
+
+    let rows = names
+        .into_iter()
+        .map(|name| {
+            let default = match config.defaults.get(name) {
+                Some(var) => Some(var.value.clone()),
+                None => config.schema.get(name).and_then(|s| s.default.clone()),
+            };
+
+            let cells = environments
+                .iter()
+                .map(|(variables, overrides, defaults)| {
+                    let value = match variables.get(name) {
+                        Some(var) => var.value.clone(),
+                        None => return Cell::Missing,
+                    };
+                    if default.is_none() {
+                        return Cell::Overridden(value);
+                    }
+                    if !overrides.contains_key(name) {
+                        return Cell::Default(value);
+                    }
+
+                    // The resolved values are compared, encrypted ones only
+                    // once decrypted as every encryption gives a new text
+                    match defaults.get(name).map(|d| &d.value) {
+                        Some(d) if *d == value => Cell::Default(value),
+                        Some(d) if d.contains(secret::PREFIX) || value.contains(secret::PREFIX) => {
+                            Cell::Unknown(value)
+                        }
+                        _ => Cell::Overridden(value),
+                    }
+                })
+                .collect();
+
+            let sensitive = environments
+                .iter()
+                .filter_map(|(variables, ..)| variables.get(name))
+                .any(|v| v.sensitive);
+
+            MatrixRow {
+                name: name.clone(),
+                default,
+                cells,
+                sensitive,
+            }
+        })
+        .collect();
+
+    Ok(Matrix { keys, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_parser::ParsedConfig;
+    use crate::{EnvVariable, EnvVariableMap};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    fn var_map(vars: &[(&str, &str)]) -> EnvVariableMap {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), EnvVariable::new(k, v.to_string())))
+            .collect()
+    }
+
+    fn config() -> EnvironmentVariablesConfig {
+        let config = ParsedConfig {
+            defaults: var_map(&[("FOO", "foo"), ("BAR", "bar"), ("API_TOKEN", "t0ken")]),
+            overrides: BTreeMap::from([
+                (
+                    "dev".to_string(),
+                    var_map(&[("FOO", "dev_foo"), ("DEBUG", "true")]),
+                ),
+                ("prod".to_string(), var_map(&[("FOO", "foo")])),
+            ]),
+            ..Default::default()
+        };
+
+        EnvironmentVariablesConfig::new(config, PathBuf::from("test.toml"))
+    }
+
+    #[test]
+    fn test_matrix() {
+        let matrix = matrix(&config(), &[]).unwrap();
+
+        assert_eq!(matrix.keys, vec!["dev", "prod"]);
+        assert_eq!(
+            matrix.rows,
+            vec![
+                MatrixRow {
+                    name: "API_TOKEN".to_owned(),
+                    default: Some("t0ken".to_owned()),
+                    cells: vec![
+                        Cell::Default("t0ken".to_owned()),
+                        Cell::Default("t0ken".to_owned())
+                    ],
+                    sensitive: true,
+                },
+                MatrixRow {
+                    name: "BAR".to_owned(),
+                    default: Some("bar".to_owned()),
+                    cells: vec![
+                        Cell::Default("bar".to_owned()),
+                        Cell::Default("bar".to_owned())
+                    ],
+                    sensitive: false,
+                },
+                MatrixRow {
+                    name: "DEBUG".to_owned(),
+                    default: None,
+                    cells: vec![Cell::Overridden("true".to_owned()), Cell::Missing],
+                    sensitive: false,
+                },
+                MatrixRow {
+                    name: "FOO".to_owned(),
+                    default: Some("foo".to_owned()),
+                    cells: vec![
+                        Cell::Overridden("dev_foo".to_owned()),
+                        Cell::Default("foo".to_owned())
+                    ],
+                    sensitive: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matrix_varying() {
+        let matrix = matrix(&config(), &["prod".to_owned()]).unwrap().varying();
+
+        assert!(matrix.rows.is_empty());
+
+        let matrix = super::matrix(&config(), &[]).unwrap().varying();
+        let names: Vec<_> = matrix.rows.iter().map(|r| r.name.as_str()).collect();
+
+        assert_eq!(names, vec!["DEBUG", "FOO"]);
+    }
+
+    #[test]
+    fn test_matrix_resolved_overrides() {
+        let token = |c: &str| format!("enc:v1:{}", c.repeat(36));
+        let config = ParsedConfig {
+            defaults: var_map(&[("HOST", "db"), ("URL", "http://db"), ("TOKEN", &token("A"))]),
+            overrides: BTreeMap::from([(
+                "dev".to_string(),
+                var_map(&[("URL", "http://${HOST}"), ("TOKEN", &token("B"))]),
+            )]),
+            ..Default::default()
+        };
+        let config = EnvironmentVariablesConfig::new(config, PathBuf::from("test.toml"))
+            .keep_encrypted(true);
+
+        let matrix = matrix(&config, &[]).unwrap();
+        let cells: Vec<_> = matrix.rows.iter().map(|r| &r.cells[0]).collect();
+
+        assert_eq!(
+            cells,
+            vec![
+                &Cell::Default("db".to_owned()),
+                &Cell::Unknown(token("B")),
+                &Cell::Default("http://db".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matrix_unknown_key() {
+        assert!(matrix(&config(), &["qa".to_owned()]).is_err());
+    }
+}
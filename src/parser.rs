@@ -0,0 +1,773 @@
+//! Config file formats.
+//!
+//! Every format implements [`ConfigParser`]. Structured formats only turn
+//! their content into a [`Value`] tree, the defaults, environments,
+//! `extends` lists and schema are then read from it the same way for all of
+//! them. The formats are looked up in a [`ParserRegistry`], by file
+//! extension first and by sniffing the content when the extension is
+//! missing or unknown. Other crates can add formats to the registry used by
+//! [`crate::parse_input_files`] with [`register`]:
+//!
+//! ```no_run
+//! use anyhow::Result;
+//! use envi::parser::{self, ConfigParser, Value};
+//!
+//! struct Hcl;
+//!
+//! impl ConfigParser for Hcl {
+//!     fn name(&self) -> &'static str {
+//!         "hcl"
+//!     }
+//!
+//!     fn extensions(&self) -> &[&'static str] {
+//!         &["hcl"]
+//!     }
+//!
+//!     fn parse_value(&self, content: &str) -> Result<Value> {
+//!         // Only `key = "value"` lines, a real parser also reads blocks
+//!         let table = content
+//!             .lines()
+//!             .filter_map(|line| line.split_once('='))
+//!             .map(|(key, value)| {
+//!                 let value = value.trim().trim_matches('"').to_owned();
+//!                 (key.trim().to_owned(), Value::String(value))
+//!             })
+//!             .collect();
+//!         Ok(Value::Table(table))
+//!     }
+//! }
+//!
+//! parser::register(Hcl);
+//! ```
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+
+use crate::file_parser::{dotenv, ini, json, properties, toml, yaml};
+use crate::schema::{self, Schema};
+use crate::EnvVariable;
+
+pub use crate::file_parser::{
+    ParsedConfig, SkippedValue, ARRAYS_KEY, ENVIRONMENTS_KEY, EXTENDS_KEY, FLATTEN_KEY, SCHEMA_KEY,
+};
+
+/// A value of a config file, independent of its format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    /// A number, as written in the config file
+    Number(String),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Table),
+    Null,
+}
+
+/// The entries of a table, in the order of the config file.
+pub type Table = Vec<(String, Value)>;
+
+impl Value {
+    /// The value of `key` if this is a table.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(t) => t.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The value of an environment variable, `None` for the values that
+    /// can't be one.
+    pub fn to_scalar(&self) -> Option<String> {
+        match self {
+            Value::String(s) | Value::Number(s) => Some(s.clone()),
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Array(_) | Value::Table(_) | Value::Null => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Table(_) => "table",
+            Value::Null => "null",
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::Number(n) => serde_json::from_str(n)
+                .ok()
+                .filter(serde_json::Value::is_number)
+                .unwrap_or_else(|| serde_json::Value::String(n.clone())),
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Array(a) => a.iter().map(Value::to_json).collect(),
+            Value::Table(t) => {
+                serde_json::Value::Object(t.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+            }
+            Value::Null => serde_json::Value::Null,
+        }
+    }
+}
+
+/// A config file format.
+pub trait ConfigParser: Send + Sync {
+    /// Name of the format, e.g. `toml`
+    fn name(&self) -> &'static str;
+
+    /// File extensions of the format, without the leading dot
+    fn extensions(&self) -> &[&'static str];
+
+    /// Whether the format handles `path` although its extension is not one
+    /// of [`ConfigParser::extensions`], e.g. because of its file name.
+    fn matches_file_name(&self, _path: &Path) -> bool {
+        false
+    }
+
+    /// Whether `content` looks like this format, used when the file
+    /// extension doesn't tell.
+    fn sniff(&self, _content: &str) -> bool {
+        false
+    }
+
+    /// Parses `content` into its top level table.
+    fn parse_value(&self, content: &str) -> Result<Value>;
+
+    /// What the format calls the type of `value`, used when reporting the
+    /// values that were skipped.
+    fn type_name(&self, value: &Value) -> &'static str {
+        value.type_name()
+    }
+
+    /// Parses the config file `file_path` with the given `content`.
+    fn parse(&self, content: &str, file_path: &Path) -> Result<ParsedConfig> {
+        let value = self
+            .parse_value(content)
+            .with_context(|| format!("failed to parse config file '{}'", file_path.display()))?;
+
+        match value {
+            Value::Table(t) => config_from_table(self, &t, file_path),
+            value => bail!(
+                "failed to parse config file '{}': the top level value is a {}, not a table",
+                file_path.display(),
+                self.type_name(&value)
+            ),
+        }
+    }
+}
+
+/// How the names of the variables in nested tables are built, from the top
+/// level [`FLATTEN_KEY`] table of a config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Flatten {
+    /// Put between the name of a table and the names inside it
+    pub separator: String,
+    pub case: NameCase,
+}
+
+impl Default for Flatten {
+    fn default() -> Self {
+        Flatten {
+            separator: "_".to_owned(),
+            case: NameCase::Upper,
+        }
+    }
+}
+
+impl Flatten {
+    /// The name of `key` inside the table named `prefix`.
+    fn name(&self, prefix: &str, key: &str) -> String {
+        format!("{}{}{}", prefix, self.separator, self.case.apply(key))
+    }
+}
+
+/// The case of the names built from nested tables.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameCase {
+    Upper,
+    Lower,
+    /// Keep the names as written in the config file
+    Preserve,
+}
+
+impl NameCase {
+    fn apply(&self, name: &str) -> String {
+        match self {
+            NameCase::Upper => name.to_uppercase(),
+            NameCase::Lower => name.to_lowercase(),
+            NameCase::Preserve => name.to_owned(),
+        }
+    }
+}
+
+/// How arrays are turned into variables, from the top level [`ARRAYS_KEY`]
+/// table of a config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Arrays {
+    /// How the items are joined, unless the schema of the variable says
+    /// otherwise
+    pub join: Join,
+}
+
+impl Default for Arrays {
+    fn default() -> Self {
+        Arrays {
+            join: Join::Separator(",".to_owned()),
+        }
+    }
+}
+
+/// How an array is turned into the value of a variable, written as the
+/// separator put between the items or `json` to encode the whole array.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "String")]
+pub enum Join {
+    Separator(String),
+    Json,
+}
+
+impl From<String> for Join {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "json" => Join::Json,
+            _ => Join::Separator(s),
+        }
+    }
+}
+
+impl Join {
+    /// The value of a variable holding `items`, which are all scalars.
+    fn apply(&self, items: &[Value]) -> String {
+        match self {
+            Join::Separator(separator) => items
+                .iter()
+                .filter_map(Value::to_scalar)
+                .collect::<Vec<_>>()
+                .join(separator),
+            Join::Json => Value::Array(items.to_vec()).to_json().to_string(),
+        }
+    }
+}
+
+/// Reads the defaults, environments, `extends` lists and schema from the top
+/// level table of a config file.
+///
+/// Without an [`ENVIRONMENTS_KEY`] table, the tables at the top level are
+/// the environments. With it, its tables are the environments and the other
+/// tables at the top level hold defaults. Nested tables are flattened, see
+/// [`Flatten`], and arrays are joined, see [`Arrays`].
+fn config_from_table<P: ConfigParser + ?Sized>(
+    parser: &P,
+    table: &Table,
+    file_path: &Path,
+) -> Result<ParsedConfig> {
+    let mut walk = Walk {
+        parser,
+        flatten: settings_from_table(table, FLATTEN_KEY, file_path)?,
+        arrays: settings_from_table(table, ARRAYS_KEY, file_path)?,
+        config: ParsedConfig {
+            schema: schema::with_schema_file(file_path, schema_from_table(table, file_path)?)?,
+            ..Default::default()
+        },
+    };
+
+    let reserved = [SCHEMA_KEY, ENVIRONMENTS_KEY, FLATTEN_KEY, ARRAYS_KEY];
+    let defaults = table
+        .iter()
+        .filter(|(key, _)| !reserved.contains(&key.as_str()));
+
+    match table.iter().find(|(key, _)| key == ENVIRONMENTS_KEY) {
+        Some((_, Value::Table(environments))) => {
+            walk.variables(None, defaults, None)?;
+
+            for (key, value) in environments.iter() {
+                match value {
+                    Value::Table(t) => walk.environment(key, t, ENVIRONMENTS_KEY)?,
+                    _ => bail!("'{}.{}' must be a table", ENVIRONMENTS_KEY, key),
+                }
+            }
+        }
+        Some(_) => bail!("'{}' must be a table", ENVIRONMENTS_KEY),
+        None => {
+            let (environments, defaults): (Vec<_>, Vec<_>) =
+                defaults.partition(|(_, value)| matches!(value, Value::Table(_)));
+
+            walk.variables(None, defaults.into_iter(), None)?;
+
+            for (key, value) in environments.into_iter() {
+                if let Value::Table(t) = value {
+                    walk.environment(key, t, "")?;
+                }
+            }
+        }
+    }
+
+    Ok(walk.config)
+}
+
+struct Walk<'a, P: ?Sized> {
+    parser: &'a P,
+    flatten: Flatten,
+    arrays: Arrays,
+    config: ParsedConfig,
+}
+
+impl<P: ConfigParser + ?Sized> Walk<'_, P> {
+    /// Adds the environment `key` defined by `table`, which is inside the
+    /// table named `parent` or at the top level if it is empty.
+    fn environment(&mut self, key: &str, table: &Table, parent: &str) -> Result<()> {
+        let path = match parent.is_empty() {
+            true => key.to_owned(),
+            false => format!("{}.{}", parent, key),
+        };
+
+        let parents = match table.iter().find(|(name, _)| name == EXTENDS_KEY) {
+            Some((_, Value::String(s))) => Some(vec![s.clone()]),
+            Some((_, Value::Array(a))) => Some(
+                a.iter()
+                    .map(|v| match v {
+                        Value::String(s) => Ok(s.clone()),
+                        _ => bail!("'{}.{}' must only contain strings", path, EXTENDS_KEY),
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            Some(_) => bail!(
+                "'{}.{}' must be a string or a list of strings",
+                path,
+                EXTENDS_KEY
+            ),
+            None => None,
+        };
+        if let Some(parents) = parents {
+            self.config.extends.insert(key.to_owned(), parents);
+        }
+
+        self.config.overrides.entry(key.to_owned()).or_default();
+        let entries = table.iter().filter(|(name, _)| name != EXTENDS_KEY);
+        self.variables(Some(key), entries, None)
+    }
+
+    /// Adds the variables of `entries` to the defaults or the environment
+    /// `environment`, prefixing their names with `prefix`.
+    fn variables<'t>(
+        &mut self,
+        environment: Option<&str>,
+        entries: impl Iterator<Item = &'t (String, Value)>,
+        prefix: Option<&str>,
+    ) -> Result<()> {
+        for (key, value) in entries {
+            let name = match prefix {
+                Some(prefix) => self.flatten.name(prefix, key),
+                None => key.clone(),
+            };
+
+            match value {
+                Value::Table(t) => {
+                    let prefix = match prefix {
+                        Some(_) => name,
+                        None => self.flatten.case.apply(key),
+                    };
+                    self.variables(environment, t.iter(), Some(&prefix))?;
+                }
+                Value::Null => self.config.skipped.push(SkippedValue::new(
+                    environment,
+                    &name,
+                    self.parser.type_name(value),
+                )),
+                _ => {
+                    let value = match value {
+                        Value::Array(items) => self.join(environment, &name, items)?,
+                        _ => value.to_scalar().unwrap_or_default(),
+                    };
+
+                    let variables = match environment {
+                        Some(key) => self.config.overrides.entry(key.to_owned()).or_default(),
+                        None => &mut self.config.defaults,
+                    };
+                    if variables.contains_key(&name) {
+                        bail!(
+                            "'{}' is defined more than once in {}",
+                            name,
+                            section_name(environment)
+                        );
+                    }
+                    variables.insert(name.clone(), EnvVariable::new(&name, value));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The value of the array variable `name`, joined as its schema or else
+    /// the [`ARRAYS_KEY`] table says.
+    fn join(&self, environment: Option<&str>, name: &str, items: &[Value]) -> Result<String> {
+        if let Some(item) = items.iter().find(|item| item.to_scalar().is_none()) {
+            bail!(
+                "'{}' in {} can't be joined into a variable, it has an item of type '{}'",
+                name,
+                section_name(environment),
+                self.parser.type_name(item)
+            );
+        }
+
+        let join = self
+            .config
+            .schema
+            .get(name)
+            .and_then(|s| s.join.as_ref())
+            .unwrap_or(&self.arrays.join);
+
+        Ok(join.apply(items))
+    }
+}
+
+fn section_name(environment: Option<&str>) -> String {
+    match environment {
+        Some(key) => format!("environment '{}'", key),
+        None => "the defaults".to_owned(),
+    }
+}
+
+/// The settings in the top level table `name`, like [`Flatten`].
+fn settings_from_table<T>(table: &Table, name: &str, file_path: &Path) -> Result<T>
+where
+    T: DeserializeOwned + Default,
+{
+    match table.iter().find(|(key, _)| key == name) {
+        Some((_, value)) => serde_json::from_value(value.to_json()).with_context(|| {
+            format!(
+                "invalid '{}' table in config file '{}'",
+                name,
+                file_path.display()
+            )
+        }),
+        None => Ok(T::default()),
+    }
+}
+
+fn schema_from_table(table: &Table, file_path: &Path) -> Result<Schema> {
+    match table.iter().find(|(key, _)| key == SCHEMA_KEY) {
+        Some((_, value)) => serde_json::from_value(value.to_json())
+            .with_context(|| format!("invalid schema in config file '{}'", file_path.display())),
+        None => Ok(Schema::new()),
+    }
+}
+
+/// The formats config files can be read in.
+#[derive(Clone)]
+pub struct ParserRegistry {
+    /// In order of precedence, the last registered first
+    parsers: Vec<Arc<dyn ConfigParser>>,
+}
+
+impl ParserRegistry {
+    /// A registry with the built-in JSON, TOML, YAML, INI, dotenv and
+    /// properties formats.
+    pub fn new() -> Self {
+        ParserRegistry {
+            parsers: vec![
+                Arc::new(json::JsonParser),
+                Arc::new(toml::TomlParser),
+                Arc::new(yaml::YamlParser),
+                Arc::new(ini::IniParser),
+                Arc::new(dotenv::DotenvParser),
+                Arc::new(properties::PropertiesParser),
+            ],
+        }
+    }
+
+    /// Adds a format. It takes precedence over the formats registered
+    /// before, for the same file extensions and when sniffing.
+    pub fn register(&mut self, parser: impl ConfigParser + 'static) {
+        self.parsers.insert(0, Arc::new(parser));
+    }
+
+    /// The format with the given name.
+    pub fn find(&self, name: &str) -> Option<Arc<dyn ConfigParser>> {
+        self.parsers.iter().find(|p| p.name() == name).cloned()
+    }
+
+    /// The format of `path` according to its file extension or name.
+    pub fn for_path(&self, path: &Path) -> Option<Arc<dyn ConfigParser>> {
+        let extension = path.extension().and_then(|e| e.to_str());
+
+        self.parsers
+            .iter()
+            .find(|p| extension.is_some_and(|e| p.extensions().contains(&e)))
+            .or_else(|| self.parsers.iter().find(|p| p.matches_file_name(path)))
+            .cloned()
+    }
+
+    /// The first format `content` looks like.
+    pub fn sniff(&self, content: &str) -> Option<Arc<dyn ConfigParser>> {
+        self.parsers.iter().find(|p| p.sniff(content)).cloned()
+    }
+
+    /// The format of the config file `path` with the given `content`, from
+    /// its file extension or else from its content.
+    pub fn detect(&self, path: &Path, content: &str) -> Result<Arc<dyn ConfigParser>> {
+        if let Some(parser) = self.for_path(path).or_else(|| self.sniff(content)) {
+            return Ok(parser);
+        }
+
+        match path.extension() {
+            Some(extension) => bail!(
+                "unsupported input file format: {}",
+                extension.to_string_lossy()
+            ),
+            None => bail!("unsupported input file format"),
+        }
+    }
+}
+
+impl Default for ParserRegistry {
+    fn default() -> Self {
+        ParserRegistry::new()
+    }
+}
+
+/// The registry used to read config files.
+pub fn registry() -> ParserRegistry {
+    global()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Adds a format to the registry used to read config files, see
+/// [`ParserRegistry::register`].
+pub fn register(parser: impl ConfigParser + 'static) {
+    global()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register(parser)
+}
+
+fn global() -> &'static RwLock<ParserRegistry> {
+    static REGISTRY: OnceLock<RwLock<ParserRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Lines;
+
+    /// `KEY value` lines, and `[env]` lines starting an environment.
+    impl ConfigParser for Lines {
+        fn name(&self) -> &'static str {
+            "lines"
+        }
+
+        fn extensions(&self) -> &[&'static str] {
+            &["lines", "json"]
+        }
+
+        fn parse_value(&self, content: &str) -> Result<Value> {
+            let mut root = Table::new();
+
+            for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                if let Some(env) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                    root.push((env.to_owned(), Value::Table(Table::new())));
+                    continue;
+                }
+
+                let (key, value) = line.split_once(' ').context("expected KEY value")?;
+                let entry = (key.to_owned(), Value::String(value.to_owned()));
+                match root.last_mut() {
+                    Some((_, Value::Table(t))) => t.push(entry),
+                    _ => root.push(entry),
+                }
+            }
+
+            Ok(Value::Table(root))
+        }
+    }
+
+    #[test]
+    fn test_custom_parser() {
+        let mut registry = ParserRegistry::new();
+        registry.register(Lines);
+
+        let parser = registry.for_path(Path::new("envi.json")).unwrap();
+        assert_eq!(parser.name(), "lines");
+
+        let config = parser
+            .parse(
+                "FOO foo\n[dev]\nFOO dev foo\nextends prod\n",
+                Path::new("envi.json"),
+            )
+            .unwrap();
+
+        assert_eq!(config.defaults["FOO"].value, "foo");
+        assert_eq!(config.overrides["dev"]["FOO"].value, "dev foo");
+        assert_eq!(config.extends["dev"], vec!["prod"]);
+    }
+
+    #[test]
+    fn test_detect() {
+        let registry = ParserRegistry::new();
+        let detect = |path: &str, content: &str| {
+            registry
+                .detect(Path::new(path), content)
+                .map(|p| p.name())
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(detect("envi.yml", ""), Ok("yaml"));
+        assert_eq!(detect(".env.dev", ""), Ok("dotenv"));
+        assert_eq!(detect("envi", "{ \"FOO\": \"foo\" }"), Ok("json"));
+        assert_eq!(detect("envi", "FOO = \"foo\"\n[dev]\n"), Ok("toml"));
+        assert_eq!(detect("envi", "FOO: foo\ndev:\n  FOO: bar\n"), Ok("yaml"));
+        assert_eq!(detect("envi", "FOO=foo\n[dev]\nFOO=bar\n"), Ok("ini"));
+        assert_eq!(detect("envi.properties", "FOO=foo\n"), Ok("properties"));
+        assert_eq!(detect("envi.conf", "export FOO=foo\n"), Ok("dotenv"));
+        assert_eq!(
+            detect("envi.conf", "not a config"),
+            Err("unsupported input file format: conf".to_owned())
+        );
+        assert_eq!(
+            detect("envi", ""),
+            Err("unsupported input file format".to_owned())
+        );
+    }
+
+    fn parse_toml(content: &str) -> Result<ParsedConfig> {
+        toml::TomlParser.parse(content, Path::new("envi.toml"))
+    }
+
+    #[test]
+    fn test_flatten_nested_tables() {
+        let config = parse_toml(
+            r#"
+            FOO = "foo"
+
+            [dev.database]
+            host = "localhost"
+            replica = { host = "replica" }
+            "#,
+        )
+        .unwrap();
+
+        let names: Vec<_> = config.overrides["dev"].keys().collect();
+        assert_eq!(names, vec!["DATABASE_HOST", "DATABASE_REPLICA_HOST"]);
+        assert_eq!(config.overrides["dev"]["DATABASE_HOST"].value, "localhost");
+    }
+
+    #[test]
+    fn test_join_arrays() {
+        let config = parse_toml(
+            r#"
+            HOSTS = ["a", "b"]
+
+            [arrays]
+            join = ";"
+
+            [schema.PATH]
+            join = ":"
+
+            [schema.PORTS]
+            join = "json"
+
+            [dev]
+            HOSTS = []
+            PATH = ["/bin", "/usr/bin"]
+            PORTS = [8080, "8081", true]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.defaults["HOSTS"].value, "a;b");
+        assert_eq!(config.overrides["dev"]["HOSTS"].value, "");
+        assert_eq!(config.overrides["dev"]["PATH"].value, "/bin:/usr/bin");
+        assert_eq!(
+            config.overrides["dev"]["PORTS"].value,
+            r#"[8080,"8081",true]"#
+        );
+        assert!(config.skipped.is_empty());
+
+        let config = parse_toml("[dev.server]\nports = [8080, 8081]").unwrap();
+        assert_eq!(config.overrides["dev"]["SERVER_PORTS"].value, "8080,8081");
+    }
+
+    #[test]
+    fn test_flatten_environments_namespace() {
+        let config = parse_toml(
+            r#"
+            FOO = "foo"
+
+            [flatten]
+            separator = "__"
+            case = "lower"
+
+            [database]
+            Host = "localhost"
+
+            [environments.dev]
+            extends = "prod"
+            FOO = "dev_foo"
+
+            [environments.dev.database]
+            Host = "dev-db"
+
+            [environments.prod]
+            "#,
+        )
+        .unwrap();
+
+        let defaults: Vec<_> = config.defaults.keys().collect();
+        assert_eq!(defaults, vec!["FOO", "database__host"]);
+        assert_eq!(
+            config.overrides.keys().collect::<Vec<_>>(),
+            vec!["dev", "prod"]
+        );
+        assert_eq!(config.overrides["dev"]["database__host"].value, "dev-db");
+        assert_eq!(config.overrides["dev"]["FOO"].value, "dev_foo");
+        assert_eq!(config.extends["dev"], vec!["prod"]);
+    }
+
+    #[test]
+    fn test_flatten_errors() {
+        let err = |content: &str| format!("{:#}", parse_toml(content).unwrap_err());
+
+        assert_eq!(
+            err("[dev]\nDATABASE_HOST = \"a\"\n[dev.database]\nhost = \"b\""),
+            "'DATABASE_HOST' is defined more than once in environment 'dev'"
+        );
+        assert_eq!(
+            err("environments = \"dev\""),
+            "'environments' must be a table"
+        );
+        assert_eq!(
+            err("[environments]\ndev = \"dev\""),
+            "'environments.dev' must be a table"
+        );
+        assert_eq!(
+            err("[environments.dev]\nextends = 1"),
+            "'environments.dev.extends' must be a string or a list of strings"
+        );
+        assert!(err("[flatten]\ncase = \"title\"")
+            .starts_with("invalid 'flatten' table in config file 'envi.toml'"));
+        assert!(err("[arrays]\nseparator = \";\"")
+            .starts_with("invalid 'arrays' table in config file 'envi.toml'"));
+        assert_eq!(
+            err("[dev]\nPORTS = [[8080], [8081]]"),
+            "'PORTS' in environment 'dev' can't be joined into a variable, it has an item of type 'array'"
+        );
+        assert_eq!(
+            err("HOSTS = [{ name = \"a\" }]"),
+            "'HOSTS' in the defaults can't be joined into a variable, it has an item of type 'table'"
+        );
+    }
+}
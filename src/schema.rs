@@ -0,0 +1,314 @@
+//! Optional description of the variables of a config.
+//!
+//! The schema is either the reserved `[schema]` table of a config file or an
+//! `envi.schema.toml` file next to it, with a table per variable:
+//!
+//! ```toml
+//! [schema.PORT]
+//! type = "integer"
+//! required = true
+//! description = "Port the server listens on"
+//!
+//! [schema.LOG_LEVEL]
+//! enum = ["debug", "info", "warn"]
+//! default = "info"
+//!
+//! [schema.PATH]
+//! join = ":"
+//! ```
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::parser::Join;
+use crate::{secret, EnvVariable, EnvVariableMap, REDACTED};
+
+/// Name of the file next to a config file that holds its schema.
+pub const SCHEMA_FILE_NAME: &str = "envi.schema.toml";
+
+pub type Schema = BTreeMap<String, VariableSchema>;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VariableSchema {
+    #[serde(rename = "type", default)]
+    pub value_type: ValueType,
+    /// Whether the variable has to be set in every environment
+    #[serde(default)]
+    pub required: bool,
+    /// Regular expression the whole value has to match
+    pub pattern: Option<String>,
+    /// The only values the variable can have
+    #[serde(rename = "enum", default, deserialize_with = "scalars")]
+    pub allowed: Option<Vec<String>>,
+    /// Value used when no environment sets the variable
+    #[serde(default, deserialize_with = "scalar")]
+    pub default: Option<String>,
+    pub description: Option<String>,
+    /// Whether the value must not be shown to bystanders
+    #[serde(default)]
+    pub secret: bool,
+    /// How the items are joined when the variable is an array, instead of
+    /// the default of the config file
+    pub join: Option<Join>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    #[default]
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueType::String => write!(f, "a string"),
+            ValueType::Integer => write!(f, "an integer"),
+            ValueType::Number => write!(f, "a number"),
+            ValueType::Boolean => write!(f, "a boolean"),
+        }
+    }
+}
+
+impl ValueType {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            ValueType::String => true,
+            ValueType::Integer => value.parse::<i64>().is_ok(),
+            ValueType::Number => value.parse::<f64>().is_ok(),
+            ValueType::Boolean => value == "true" || value == "false",
+        }
+    }
+}
+
+/// Defaults and enum values can be written as any scalar, just like the
+/// variables themselves.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scalar::Bool(b) => write!(f, "{}", b),
+            Scalar::Integer(i) => write!(f, "{}", i),
+            Scalar::Float(n) => write!(f, "{}", n),
+            Scalar::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+fn scalar<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let value = Option::<Scalar>::deserialize(deserializer)?;
+    Ok(value.map(|v| v.to_string()))
+}
+
+fn scalars<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<String>>, D::Error> {
+    let values = Option::<Vec<Scalar>>::deserialize(deserializer)?;
+    Ok(values.map(|v| v.iter().map(|s| s.to_string()).collect()))
+}
+
+pub fn parse_schema_file(path: &Path) -> Result<Schema> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read schema file `{}`", path.display()))?;
+
+    toml::from_str(&content)
+        .with_context(|| format!("failed to parse schema file '{}'", path.display()))
+}
+
+/// The schema of the config file (or directory) `config_path`: the one in
+/// the [`SCHEMA_FILE_NAME`] file next to it, overridden by the `schema` read
+/// from the config file itself.
+pub(crate) fn with_schema_file(config_path: &Path, schema: Schema) -> Result<Schema> {
+    let dir = match config_path.is_dir() {
+        true => Some(config_path),
+        false if crate::is_stdin(config_path) => None,
+        false => config_path.parent(),
+    };
+
+    match dir.map(|d| d.join(SCHEMA_FILE_NAME)) {
+        Some(schema_file) if schema_file.is_file() => {
+            let mut res = parse_schema_file(&schema_file)?;
+            res.extend(schema);
+            Ok(res)
+        }
+        _ => Ok(schema),
+    }
+}
+
+/// Adds the schema defaults of the variables missing from `variables`.
+pub(crate) fn apply_defaults(schema: &Schema, variables: &mut EnvVariableMap) {
+    for (name, var_schema) in schema.iter() {
+        if let Some(default) = &var_schema.default {
+            variables
+                .entry(name.clone())
+                .or_insert_with(|| EnvVariable::new(name, default.clone()));
+        }
+    }
+}
+
+/// Checks `variables` against the schema, returning a message for every
+/// variable that doesn't match it.
+pub(crate) fn check(schema: &Schema, variables: &EnvVariableMap) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for (name, var_schema) in schema.iter() {
+        let var = match variables.get(name) {
+            Some(var) => var,
+            None => {
+                if var_schema.required {
+                    errors.push(format!("'{}' is required but not set", name));
+                }
+                continue;
+            }
+        };
+        let value = &var.value;
+        // Encrypted values, and the ones built from them, can only be
+        // checked once decrypted
+        if value.contains(secret::PREFIX) {
+            continue;
+        }
+        // The messages end up in CI logs, they must not leak secrets
+        let shown = match var.sensitive {
+            true => REDACTED,
+            false => value.as_str(),
+        };
+
+        if !var_schema.value_type.matches(value) {
+            errors.push(format!(
+                "'{}' must be {}, got '{}'",
+                name, var_schema.value_type, shown
+            ));
+        }
+
+        if let Some(pattern) = &var_schema.pattern {
+            match Regex::new(&format!("^(?:{})$", pattern)) {
+                Ok(re) if re.is_match(value) => (),
+                Ok(_) => errors.push(format!(
+                    "'{}' must match the pattern '{}', got '{}'",
+                    name, pattern, shown
+                )),
+                Err(e) => errors.push(format!("'{}' has an invalid pattern: {}", name, e)),
+            }
+        }
+
+        if let Some(allowed) = &var_schema.allowed {
+            if !allowed.contains(value) {
+                errors.push(format!(
+                    "'{}' must be one of '{}', got '{}'",
+                    name,
+                    allowed.join("', '"),
+                    shown
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var_map(vars: &[(&str, &str)]) -> EnvVariableMap {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), EnvVariable::new(k, v.to_string())))
+            .collect()
+    }
+
+    const SCHEMA: &str = r#"
+        [PORT]
+        type = "integer"
+        required = true
+        description = "Port to listen on"
+
+        [LOG_LEVEL]
+        enum = ["debug", "info"]
+        default = "info"
+
+        [VERSION]
+        pattern = '\d+\.\d+'
+
+        [DEBUG]
+        type = "boolean"
+        default = false
+        secret = true
+    "#;
+
+    #[test]
+    fn test_parse_schema() {
+        let schema: Schema = toml::from_str(SCHEMA).unwrap();
+
+        assert_eq!(
+            schema["PORT"],
+            VariableSchema {
+                value_type: ValueType::Integer,
+                required: true,
+                description: Some("Port to listen on".to_owned()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(schema["DEBUG"].default, Some("false".to_owned()));
+        assert!(schema["DEBUG"].secret);
+
+        let res: Result<Schema, _> = toml::from_str("[PORT]\ntype = \"int\"");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let schema: Schema = toml::from_str(SCHEMA).unwrap();
+        let mut variables = var_map(&[("DEBUG", "true")]);
+
+        apply_defaults(&schema, &mut variables);
+
+        assert_eq!(
+            variables,
+            var_map(&[("DEBUG", "true"), ("LOG_LEVEL", "info")])
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let schema: Schema = toml::from_str(SCHEMA).unwrap();
+
+        let valid = var_map(&[("PORT", "8080"), ("LOG_LEVEL", "debug"), ("VERSION", "1.2")]);
+        assert!(check(&schema, &valid).is_empty());
+
+        let invalid = var_map(&[
+            ("DEBUG", "yes"),
+            ("LOG_LEVEL", "trace"),
+            ("VERSION", "v1.2"),
+        ]);
+        assert_eq!(
+            check(&schema, &invalid),
+            vec![
+                "'DEBUG' must be a boolean, got 'yes'",
+                "'LOG_LEVEL' must be one of 'debug', 'info', got 'trace'",
+                "'PORT' is required but not set",
+                r"'VERSION' must match the pattern '\d+\.\d+', got 'v1.2'",
+            ]
+        );
+
+        let mut secret = var_map(&[("PORT", "8080"), ("VERSION", "hunter2")]);
+        secret.get_mut("VERSION").unwrap().sensitive = true;
+        assert_eq!(
+            check(&schema, &secret),
+            vec![r"'VERSION' must match the pattern '\d+\.\d+', got '****'"]
+        );
+    }
+}
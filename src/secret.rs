@@ -0,0 +1,295 @@
+//! Encrypted values, stored in config files as `enc:v1:<base64>`.
+//!
+//! Values are encrypted with ChaCha20-Poly1305 using a random 96 bit nonce
+//! that is stored in front of the ciphertext. The variable name is used as
+//! associated data, so an encrypted value can't be moved to another variable.
+//!
+//! The 256 bit key is read from the file in `ENVI_KEY_FILE`, or from
+//! `$XDG_CONFIG_HOME/envi/key` (`~/.config/envi/key`), base64 encoded.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::env;
+use std::io::Write;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use crate::edit::{self, Section};
+
+/// Prefix of encrypted values.
+pub const PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Where the key is read from and written to.
+pub fn key_file_path() -> Result<PathBuf> {
+    match env::var_os("ENVI_KEY_FILE") {
+        Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => crate::user_config_dir()
+            .map(|dir| dir.join("key"))
+            .ok_or_else(|| anyhow!("could not determine the key file, set ENVI_KEY_FILE")),
+    }
+}
+
+pub struct SecretKey {
+    key: Key,
+    cipher: ChaCha20Poly1305,
+    /// The file the key is read from and saved to
+    pub path: PathBuf,
+}
+
+impl SecretKey {
+    /// Reads the key from its file.
+    pub fn load() -> Result<Self> {
+        let path = key_file_path()?;
+
+        if !path.exists() {
+            bail!(
+                "no key file found at '{}', set ENVI_KEY_FILE to the key used to encrypt the values",
+                path.display()
+            );
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read key file `{}`", path.display()))?;
+
+        let key = STANDARD
+            .decode(content.trim())
+            .ok()
+            .filter(|k| k.len() == 32)
+            .ok_or_else(|| anyhow!("key file '{}' does not contain a valid key", path.display()))?;
+
+        Ok(SecretKey::new(*Key::from_slice(&key), path))
+    }
+
+    /// A new random key, to be written to `path` with [`SecretKey::save`].
+    pub fn generate(path: PathBuf) -> Self {
+        SecretKey::new(ChaCha20Poly1305::generate_key(&mut OsRng), path)
+    }
+
+    fn new(key: Key, path: PathBuf) -> Self {
+        SecretKey {
+            cipher: ChaCha20Poly1305::new(&key),
+            key,
+            path,
+        }
+    }
+
+    /// Reads the key from its file, creating a new one first if there is
+    /// none yet.
+    pub fn load_or_create() -> Result<Self> {
+        let path = key_file_path()?;
+
+        if !path.exists() {
+            let key = SecretKey::generate(path);
+            key.save()?;
+            return Ok(key);
+        }
+
+        SecretKey::load()
+    }
+
+    /// Writes the key to its file, readable by the current user only.
+    pub fn save(&self) -> Result<()> {
+        let path = &self.path;
+
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("could not create directory `{}`", dir.display()))?;
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("could not write to file `{}`", path.display()))?;
+
+        writeln!(file, "{}", STANDARD.encode(self.key))
+            .with_context(|| format!("could not write to file `{}`", path.display()))
+    }
+
+    pub fn encrypt(&self, name: &str, value: &str) -> Result<String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: value.as_bytes(),
+            aad: name.as_bytes(),
+        };
+
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| anyhow!("could not encrypt the value of '{}'", name))?;
+
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+
+        Ok(format!("{}{}", PREFIX, STANDARD.encode(data)))
+    }
+
+    pub fn decrypt(&self, name: &str, value: &str) -> Result<String> {
+        let data = decode(name, value)?;
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: name.as_bytes(),
+        };
+
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| {
+                anyhow!(
+                    "could not decrypt the value of '{}', it was not encrypted with the key in '{}'",
+                    name,
+                    self.path.display()
+                )
+            })?;
+
+        String::from_utf8(plaintext)
+            .map_err(|_| anyhow!("the decrypted value of '{}' is not valid UTF-8", name))
+    }
+}
+
+/// Fails if the encrypted `value` of the variable `name` can't be the
+/// output of [`SecretKey::encrypt`], without needing the key.
+pub fn check_encrypted(name: &str, value: &str) -> Result<()> {
+    decode(name, value).map(|_| ())
+}
+
+/// The nonce followed by the ciphertext.
+fn decode(name: &str, value: &str) -> Result<Vec<u8>> {
+    value
+        .strip_prefix(PREFIX)
+        .and_then(|v| STANDARD.decode(v).ok())
+        .filter(|d| d.len() > NONCE_LEN)
+        .ok_or_else(|| anyhow!("the encrypted value of '{}' is malformed", name))
+}
+
+/// Where [`rotate_key`] keeps the previous key.
+pub fn backup_path(key_file: &Path) -> PathBuf {
+    let mut path = key_file.as_os_str().to_owned();
+    path.push(".old");
+    PathBuf::from(path)
+}
+
+/// Re-encrypts the encrypted values of the given config files, read in the
+/// given `format` or the detected one, with a new key, returning how many
+/// values were re-encrypted. The previous key is kept next to the new one,
+/// see [`backup_path`].
+pub fn rotate_key(files: &[PathBuf], format: Option<&str>) -> Result<usize> {
+    let old_key = SecretKey::load()?;
+    let new_key = SecretKey::generate(old_key.path.clone());
+
+    // Everything is decrypted before anything is written, so a value that
+    // can't be decrypted leaves the key and the config files untouched.
+    let mut updates = Vec::new();
+
+    for file in files.iter() {
+        if crate::is_stdin(file) {
+            bail!("the config read from stdin can't be re-encrypted");
+        }
+
+        let config = crate::parse_config_file(file, format)?;
+
+        let sections = iter::once((None, &config.defaults)).chain(
+            config
+                .overrides
+                .iter()
+                .map(|(key, vars)| (Some(key.clone()), vars)),
+        );
+
+        for (key, vars) in sections {
+            let section = match &key {
+                Some(key) => Section::Environment(key),
+                None => Section::Defaults,
+            };
+
+            for var in vars.values().filter(|v| is_encrypted(&v.value)) {
+                edit::check_not_flattened(file, format, section, &var.key)?;
+
+                let value = old_key.decrypt(&var.key, &var.value)?;
+                let encrypted = new_key.encrypt(&var.key, &value)?;
+                updates.push((file, key.clone(), var.key.clone(), encrypted));
+            }
+        }
+    }
+
+    let backup = backup_path(&old_key.path);
+    std::fs::copy(&old_key.path, &backup)
+        .with_context(|| format!("could not write to file `{}`", backup.display()))?;
+    new_key.save()?;
+
+    for (file, key, name, value) in updates.iter() {
+        let section = match key {
+            Some(key) => Section::Environment(key),
+            None => Section::Defaults,
+        };
+        edit::set_variable(file, format, section, name, value)?;
+    }
+
+    Ok(updates.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> SecretKey {
+        SecretKey::generate(PathBuf::from("key"))
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = key();
+
+        let encrypted = key.encrypt("DB_PASSWORD", "s3cr3t $tuff").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("s3cr3t"));
+        assert_ne!(
+            encrypted,
+            key.encrypt("DB_PASSWORD", "s3cr3t $tuff").unwrap()
+        );
+
+        assert_eq!(
+            key.decrypt("DB_PASSWORD", &encrypted).unwrap(),
+            "s3cr3t $tuff"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_errors() {
+        let key = key();
+        let encrypted = key.encrypt("DB_PASSWORD", "secret").unwrap();
+
+        assert_eq!(
+            key.decrypt("OTHER", &encrypted).unwrap_err().to_string(),
+            "could not decrypt the value of 'OTHER', it was not encrypted with the key in 'key'"
+        );
+        assert!(SecretKey::generate(PathBuf::from("key"))
+            .decrypt("DB_PASSWORD", &encrypted)
+            .is_err());
+        assert_eq!(
+            key.decrypt("DB_PASSWORD", "enc:v1:nope")
+                .unwrap_err()
+                .to_string(),
+            "the encrypted value of 'DB_PASSWORD' is malformed"
+        );
+
+        assert!(check_encrypted("DB_PASSWORD", &encrypted).is_ok());
+        assert!(check_encrypted("DB_PASSWORD", "enc:v1:nope").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Config files the user allowed to be loaded automatically.
+//!
+//! `exec` and the shell hook only load discovered config files whose
+//! content was approved with `envi allow`. The SHA-256 hash of every allowed
+//! file is kept in the file in `ENVI_TRUST_FILE`, or in
+//! `$XDG_CONFIG_HOME/envi/trusted` (`~/.config/envi/trusted`), one
+//! `<hash>  <path>` line per file like the output of `sha256sum`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::schema::SCHEMA_FILE_NAME;
+
+/// Where the hashes of the allowed files are read from and written to.
+pub fn trust_file_path() -> Result<PathBuf> {
+    match env::var_os("ENVI_TRUST_FILE") {
+        Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => crate::user_config_dir()
+            .map(|dir| dir.join("trusted"))
+            .ok_or_else(|| anyhow!("could not determine the trust file, set ENVI_TRUST_FILE")),
+    }
+}
+
+pub struct TrustStore {
+    path: PathBuf,
+    hashes: BTreeMap<PathBuf, String>,
+}
+
+impl TrustStore {
+    pub fn load() -> Result<Self> {
+        let path = trust_file_path()?;
+
+        let mut hashes = BTreeMap::new();
+
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("could not read trust file `{}`", path.display()))?;
+
+            for line in content.lines().filter(|l| !l.is_empty()) {
+                match line.split_once("  ") {
+                    Some((hash, file)) => hashes.insert(PathBuf::from(file), hash.to_owned()),
+                    None => bail!("trust file '{}' is malformed", path.display()),
+                };
+            }
+        }
+
+        Ok(TrustStore { path, hashes })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = &self.path;
+
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("could not create directory `{}`", dir.display()))?;
+        }
+
+        let mut content = String::new();
+        for (file, hash) in self.hashes.iter() {
+            writeln!(content, "{}  {}", hash, file.display())?;
+        }
+
+        std::fs::write(path, content)
+            .with_context(|| format!("could not write to file `{}`", path.display()))
+    }
+
+    /// Records the current content of `file` as allowed.
+    pub fn allow(&mut self, file: &Path) -> Result<()> {
+        let (file, hash) = hash_file(file)?;
+        self.hashes.insert(file, hash);
+        Ok(())
+    }
+
+    /// Forgets `file`, returning whether it was allowed before.
+    pub fn deny(&mut self, file: &Path) -> Result<bool> {
+        let file = canonicalize(file)?;
+        Ok(self.hashes.remove(&file).is_some())
+    }
+
+    /// Fails if `file` was not allowed or changed since.
+    pub fn check(&self, file: &Path) -> Result<()> {
+        let (canonical, hash) = hash_file(file)?;
+
+        match self.hashes.get(&canonical) {
+            Some(allowed) if *allowed == hash => Ok(()),
+            Some(_) => bail!(
+                "'{}' changed since it was allowed, review it and run `envi allow` to load it",
+                file.display()
+            ),
+            None => bail!(
+                "'{}' is not allowed, review it and run `envi allow` to load it",
+                file.display()
+            ),
+        }
+    }
+}
+
+/// The files `config_files` are loaded from: the config files and their
+/// `envi.schema.toml` files.
+pub fn files_to_trust(config_files: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    for file in config_files.iter() {
+        files.push(file.clone());
+
+        let schema_file = file
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(SCHEMA_FILE_NAME);
+        if schema_file.is_file() && !files.contains(&schema_file) {
+            files.push(schema_file);
+        }
+    }
+
+    files
+}
+
+/// Fails if any of the files `config_files` are loaded from is not allowed.
+/// The files in the user's config directory are always allowed.
+pub fn check(config_files: &[PathBuf]) -> Result<()> {
+    let store = TrustStore::load()?;
+    let user_dir = crate::user_config_dir().and_then(|d| d.canonicalize().ok());
+
+    for file in files_to_trust(config_files).iter() {
+        let in_user_dir = match (&user_dir, file.canonicalize()) {
+            (Some(dir), Ok(file)) => file.starts_with(dir),
+            _ => false,
+        };
+
+        if !in_user_dir {
+            store.check(file)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn canonicalize(file: &Path) -> Result<PathBuf> {
+    file.canonicalize()
+        .with_context(|| format!("could not read file `{}`", file.display()))
+}
+
+fn hash_file(file: &Path) -> Result<(PathBuf, String)> {
+    let canonical = canonicalize(file)?;
+
+    let content = std::fs::read(&canonical)
+        .with_context(|| format!("could not read file `{}`", file.display()))?;
+
+    let hash = Sha256::digest(content)
+        .iter()
+        .fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        });
+
+    Ok((canonical, hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_store() {
+        let dir = env::temp_dir().join(format!("envi-trust-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(".envi.toml");
+        std::fs::write(&file, "FOO = \"foo\"\n").unwrap();
+
+        let mut store = TrustStore {
+            path: dir.join("trusted"),
+            hashes: BTreeMap::new(),
+        };
+
+        assert!(store
+            .check(&file)
+            .unwrap_err()
+            .to_string()
+            .ends_with("is not allowed, review it and run `envi allow` to load it"));
+
+        store.allow(&file).unwrap();
+        store.check(&file).unwrap();
+
+        std::fs::write(&file, "FOO = \"changed\"\n").unwrap();
+        assert!(store
+            .check(&file)
+            .unwrap_err()
+            .to_string()
+            .contains("changed since it was allowed"));
+
+        assert!(store.deny(&file).unwrap());
+        assert!(!store.deny(&file).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
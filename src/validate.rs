@@ -0,0 +1,246 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::EnvironmentVariablesConfig;
+
+/// A problem found in a config by [`validate`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "kebab-case")]
+pub enum Issue {
+    /// The name can't be exported by POSIX shells
+    InvalidName {
+        environment: Option<String>,
+        name: String,
+    },
+    /// The value has a type that can't be an environment variable and was dropped
+    UnsupportedValue {
+        environment: Option<String>,
+        name: String,
+        value_type: &'static str,
+    },
+    /// The environment overrides a default with the same value
+    IdenticalOverride { environment: String, name: String },
+    /// Other environments define a variable this one doesn't have
+    MissingVariable {
+        environment: String,
+        name: String,
+        defined_in: Vec<String>,
+    },
+    /// The variables of the environment can't be resolved
+    InvalidEnvironment { environment: String, error: String },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::InvalidName { environment, name } => write!(
+                f,
+                "{}: '{}' is not a valid variable name, it must only contain letters, digits and '_' and not start with a digit",
+                section(environment),
+                name
+            ),
+            Issue::UnsupportedValue {
+                environment,
+                name,
+                value_type,
+            } => write!(
+                f,
+                "{}: '{}' has an unsupported value ({}) and was dropped",
+                section(environment),
+                name,
+                value_type
+            ),
+            Issue::IdenticalOverride { environment, name } => write!(
+                f,
+                "{}: '{}' overrides the default with the same value",
+                environment, name
+            ),
+            Issue::MissingVariable {
+                environment,
+                name,
+                defined_in,
+            } => write!(
+                f,
+                "{}: '{}' is not defined, but it is in '{}'",
+                environment,
+                name,
+                defined_in.join("', '")
+            ),
+            Issue::InvalidEnvironment { environment, error } => {
+                write!(f, "{}: {}", environment, error)
+            }
+        }
+    }
+}
+
+fn section(environment: &Option<String>) -> &str {
+    environment.as_deref().unwrap_or("defaults")
+}
+
+/// Returns whether `name` can be exported by a POSIX shell.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Checks `config` for problems that don't stop it from being used but are
+/// likely mistakes.
+pub fn validate(config: &EnvironmentVariablesConfig) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let names = config.defaults.keys().map(|name| (None, name)).chain(
+        config
+            .overrides
+            .iter()
+            .flat_map(|(key, vars)| vars.keys().map(move |name| (Some(key), name))),
+    );
+    for (environment, name) in names {
+        if !is_valid_name(name) {
+            issues.push(Issue::InvalidName {
+                environment: environment.cloned(),
+                name: name.clone(),
+            });
+        }
+    }
+
+    for skipped in config.skipped.iter() {
+        issues.push(Issue::UnsupportedValue {
+            environment: skipped.environment.clone(),
+            name: skipped.name.clone(),
+            value_type: skipped.value_type,
+        });
+    }
+
+    for (key, vars) in config.overrides.iter() {
+        for var in vars.values() {
+            if config.defaults.get(&var.key).map(|d| &d.value) == Some(&var.value) {
+                issues.push(Issue::IdenticalOverride {
+                    environment: key.clone(),
+                    name: var.key.clone(),
+                });
+            }
+        }
+    }
+
+    // Variables every environment gets from the defaults can't be missing,
+    // so only the ones coming from the (inherited) overrides are compared.
+    let mut defined: BTreeMap<&String, BTreeSet<String>> = BTreeMap::new();
+    for key in config.overrides.keys() {
+        // Encrypted values are not decrypted, so no key is needed
+        match config.resolve_variables(key, false) {
+            Ok(_) => {
+                let names = config.resolve_overrides(key, &mut Vec::new()).unwrap();
+                defined.insert(key, names.into_keys().collect());
+            }
+            Err(e) => issues.push(Issue::InvalidEnvironment {
+                environment: key.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    let all_names: BTreeSet<_> = defined.values().flatten().collect();
+    for (key, names) in defined.iter() {
+        for name in all_names.iter() {
+            let has_default = config.defaults.contains_key(*name)
+                || config
+                    .schema
+                    .get(*name)
+                    .and_then(|s| s.default.as_ref())
+                    .is_some();
+            if names.contains(*name) || has_default {
+                continue;
+            }
+            issues.push(Issue::MissingVariable {
+                environment: key.to_string(),
+                name: name.to_string(),
+                defined_in: defined
+                    .iter()
+                    .filter(|(_, names)| names.contains(*name))
+                    .map(|(key, _)| key.to_string())
+                    .collect(),
+            });
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_parser::{ParsedConfig, SkippedValue};
+    use crate::{EnvVariable, EnvVariableMap};
+    use std::path::PathBuf;
+
+    fn var_map(vars: &[(&str, &str)]) -> EnvVariableMap {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), EnvVariable::new(k, v.to_string())))
+            .collect()
+    }
+
+    fn validate_config(config: ParsedConfig) -> Vec<String> {
+        let config = EnvironmentVariablesConfig::new(config, PathBuf::from("test.toml"));
+        validate(&config).iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("FOO"));
+        assert!(is_valid_name("_foo_1"));
+        assert!(!is_valid_name("my-var"));
+        assert!(!is_valid_name("1FOO"));
+        assert!(!is_valid_name(""));
+    }
+
+    #[test]
+    fn test_validate_valid_config() {
+        let config = ParsedConfig {
+            defaults: var_map(&[("FOO", "foo")]),
+            overrides: BTreeMap::from([
+                ("dev".to_string(), var_map(&[("FOO", "dev_foo")])),
+                ("prod".to_string(), var_map(&[])),
+            ]),
+            ..Default::default()
+        };
+
+        assert!(validate_config(config).is_empty());
+    }
+
+    #[test]
+    fn test_validate_issues() {
+        let config = ParsedConfig {
+            defaults: var_map(&[("FOO", "foo"), ("my-var", "x")]),
+            overrides: BTreeMap::from([
+                (
+                    "dev".to_string(),
+                    var_map(&[("FOO", "foo"), ("DEBUG", "true"), ("1BAR", "bar")]),
+                ),
+                ("prod".to_string(), var_map(&[("URL", "${MISSING}")])),
+                ("staging".to_string(), var_map(&[])),
+            ]),
+            skipped: vec![SkippedValue::new(Some("dev"), "PORTS", "null")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            validate_config(config),
+            vec![
+                "defaults: 'my-var' is not a valid variable name, it must only contain letters, digits and '_' and not start with a digit",
+                "dev: '1BAR' is not a valid variable name, it must only contain letters, digits and '_' and not start with a digit",
+                "dev: 'PORTS' has an unsupported value (null) and was dropped",
+                "dev: 'FOO' overrides the default with the same value",
+                "prod: undefined variable 'MISSING' referenced in the value of 'URL'",
+                "staging: '1BAR' is not defined, but it is in 'dev'",
+                "staging: 'DEBUG' is not defined, but it is in 'dev'",
+            ]
+        );
+    }
+}